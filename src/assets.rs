@@ -0,0 +1,204 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use rocket::{
+    fs::NamedFile,
+    http::{ContentType, Header},
+    Responder,
+};
+use serde::{Deserialize, Serialize};
+use tracing::error;
+use uuid::Uuid;
+
+use crate::event::Shareable;
+
+/// The `assets` section of the Rocket config.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct AssetsConfig {
+    /// Where uploads and their index are kept.
+    pub dir: PathBuf,
+    pub max_size_bytes: u64,
+    /// Media types accepted for upload, out of `image/png`, `image/jpeg`,
+    /// `image/gif` and `image/webp`.
+    pub content_types: Vec<String>,
+}
+impl Default for AssetsConfig {
+    fn default() -> Self {
+        Self {
+            dir: "assets".into(),
+            max_size_bytes: 5 * 1024 * 1024,
+            content_types: ["image/png", "image/jpeg", "image/gif", "image/webp"]
+                .map(String::from)
+                .into(),
+        }
+    }
+}
+
+/// An uploaded file, such as a team logo or sponsor image.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Asset {
+    pub id: String,
+    pub name: Option<String>,
+    pub content_type: String,
+    pub size: u64,
+}
+
+/// Uploaded media, stored on disk next to an `index.json` describing it.
+#[derive(Debug, Clone)]
+pub struct Assets {
+    dir: PathBuf,
+    max_size: u64,
+    content_types: Vec<String>,
+    index: Shareable<Vec<Asset>>,
+}
+impl Assets {
+    pub fn new(config: AssetsConfig) -> Self {
+        if let Err(e) = fs::create_dir_all(&config.dir) {
+            panic!(
+                "invalid `assets` config: can't create {}: {e}",
+                config.dir.display()
+            );
+        }
+        Self {
+            index: load_index(&config.dir.join("index.json")).into(),
+            dir: config.dir,
+            max_size: config.max_size_bytes,
+            content_types: config.content_types,
+        }
+    }
+    pub fn max_size(&self) -> u64 {
+        self.max_size
+    }
+    /// The media type of an upload, going by its content rather than what
+    /// the uploader claims, if uploads may have it.
+    pub fn accepts(&self, bytes: &[u8]) -> Option<&'static str> {
+        let media_type = sniff(bytes)?;
+        self.content_types
+            .iter()
+            .any(|accepted| accepted.eq_ignore_ascii_case(media_type))
+            .then_some(media_type)
+    }
+    pub fn list(&self) -> Vec<Asset> {
+        self.index.data.lock().unwrap().clone()
+    }
+    pub fn get(&self, id: &str) -> Option<Asset> {
+        let index = self.index.data.lock().unwrap();
+        index.iter().find(|asset| asset.id == id).cloned()
+    }
+    pub fn path(&self, asset: &Asset) -> PathBuf {
+        self.dir.join(&asset.id)
+    }
+    pub async fn open(&self, asset: &Asset) -> Option<AssetFile> {
+        Some(AssetFile {
+            file: NamedFile::open(self.path(asset)).await.ok()?,
+            content_type: ContentType::parse_flexible(&asset.content_type)?,
+            nosniff: Header::new("X-Content-Type-Options", "nosniff"),
+            csp: Header::new("Content-Security-Policy", "default-src 'none'; sandbox"),
+        })
+    }
+    pub fn save(
+        &self,
+        content_type: String,
+        name: Option<String>,
+        bytes: &[u8],
+    ) -> io::Result<Asset> {
+        let asset = Asset {
+            id: Uuid::new_v4().to_string(),
+            name,
+            content_type,
+            size: bytes.len() as u64,
+        };
+        fs::write(self.path(&asset), bytes)?;
+        let mut index = self.index.data.lock().unwrap();
+        index.push(asset.clone());
+        self.write_index(&index)?;
+        Ok(asset)
+    }
+    /// Returns false for unknown ids.
+    pub fn delete(&self, id: &str) -> io::Result<bool> {
+        let mut index = self.index.data.lock().unwrap();
+        let Some(position) = index.iter().position(|asset| asset.id == id) else {
+            return Ok(false);
+        };
+        let asset = index.remove(position);
+        self.write_index(&index)?;
+        if let Err(e) = fs::remove_file(self.path(&asset)) {
+            error!(error = %e, id, "assets: failed to remove file");
+        }
+        Ok(true)
+    }
+    fn write_index(&self, index: &[Asset]) -> io::Result<()> {
+        let json = serde_json::to_string_pretty(index).expect("asset index serializes");
+        fs::write(self.dir.join("index.json"), json)
+    }
+}
+
+/// An asset's file, served so browsers only ever treat it as an image.
+#[derive(Responder)]
+pub struct AssetFile {
+    file: NamedFile,
+    content_type: ContentType,
+    nosniff: Header<'static>,
+    csp: Header<'static>,
+}
+
+/// Recognises the image formats assets may have by their magic bytes.
+fn sniff(bytes: &[u8]) -> Option<&'static str> {
+    match bytes {
+        [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n', ..] => Some("image/png"),
+        [0xff, 0xd8, 0xff, ..] => Some("image/jpeg"),
+        [b'G', b'I', b'F', b'8', b'7' | b'9', b'a', ..] => Some("image/gif"),
+        [b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'E', b'B', b'P', ..] => Some("image/webp"),
+        _ => None,
+    }
+}
+
+fn load_index(path: &Path) -> Vec<Asset> {
+    let Ok(index) = fs::read_to_string(path) else {
+        return vec![];
+    };
+    serde_json::from_str(&index).unwrap_or_else(|e| {
+        error!(path = %path.display(), error = %e, "assets: ignoring invalid index");
+        vec![]
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::*;
+
+    const PNG: &[u8] = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR";
+
+    #[test]
+    fn sniffs_images_by_magic_bytes() {
+        assert_eq!(sniff(PNG), Some("image/png"));
+        assert_eq!(sniff(b"\xff\xd8\xff\xe0\0\x10JFIF"), Some("image/jpeg"));
+        assert_eq!(sniff(b"GIF87a\x01\0"), Some("image/gif"));
+        assert_eq!(sniff(b"GIF89a\x01\0"), Some("image/gif"));
+        assert_eq!(sniff(b"RIFF\x24\0\0\0WEBPVP8 "), Some("image/webp"));
+        assert_eq!(sniff(b"RIFF\x24\0\0\0WAVEfmt "), None);
+        assert_eq!(sniff(b"<svg xmlns=\"http://www.w3.org/2000/svg\"/>"), None);
+        assert_eq!(sniff(b"<html><script>alert(1)</script>"), None);
+        assert_eq!(sniff(&PNG[..7]), None);
+        assert_eq!(sniff(b""), None);
+    }
+
+    #[test]
+    fn accepts_only_configured_types() {
+        let dir = env::temp_dir().join(format!("assets-{}", Uuid::new_v4()));
+        let assets = Assets::new(AssetsConfig {
+            dir: dir.clone(),
+            content_types: vec!["IMAGE/PNG".into()],
+            ..Default::default()
+        });
+        assert_eq!(assets.accepts(PNG), Some("image/png"));
+        assert_eq!(assets.accepts(b"GIF89a\x01\0"), None);
+        assert_eq!(assets.accepts(b"not an image"), None);
+        let _ = fs::remove_dir_all(dir);
+    }
+}
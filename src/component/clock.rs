@@ -5,8 +5,14 @@ use rocket::serde::Serialize;
 use serde::Deserialize;
 use serde_json::{json, value::Serializer};
 use serde_millis::Milliseconds;
+use tracing::warn;
 
-use crate::*;
+use crate::{
+    metrics::METRICS,
+    scoreboard::{ComponentState, ScoreboardComponent},
+    siren::{HornCue, Horns},
+    *,
+};
 
 use super::*;
 
@@ -19,20 +25,59 @@ pub struct ClockComponent {
     pub last_state_change: Instant,
     #[serde(with = "serde_millis")]
     pub last_time_remaining: Duration,
+    #[serde(skip)]
+    max: Option<Duration>,
 }
 impl ClockComponent {
-    fn new(name: String) -> Self {
+    fn new(name: String, max: Option<Duration>) -> Self {
         ClockComponent {
             name,
             state: ClockState::Stopped,
             last_state_change: Instant::now(),
             last_time_remaining: Duration::from_secs(0),
+            max,
         }
     }
-    fn process_event(&mut self, event: &LogEvent) {
+    /// Rejects events that would put more than the maximum on the clock.
+    pub fn check(&self, event: &ClockEvent, now: Instant) -> Result<(), String> {
+        use ClockEvent as E;
+        let Some(max) = self.max else {
+            return Ok(());
+        };
+        let time = match *event {
+            E::Set(time) | E::Start(Some(time)) | E::Stop(Some(time)) => time,
+            E::Increment(time) => self.get_time_remaining_at(now).saturating_add(time),
+            _ => return Ok(()),
+        };
+        if time <= max {
+            return Ok(());
+        }
+        Err(format!(
+            "{} can't go above {} ms, not {} ms",
+            self.name,
+            max.as_millis(),
+            time.as_millis()
+        ))
+    }
+    /// Why `event` would be ignored for going out of bounds, if it would.
+    pub fn validate(&self, event: &LogEvent) -> Result<(), String> {
+        match &event.event {
+            Event::Clock(clock_event) => self.check(clock_event, event.timestamp),
+            _ => Ok(()),
+        }
+    }
+    pub fn process_event(&mut self, event: &LogEvent) {
         use ClockEvent as E;
         use ClockState as S;
 
+        if let Event::Restore(ComponentState::Clock(clock)) = &event.event {
+            *self = ClockComponent {
+                name: std::mem::take(&mut self.name),
+                max: self.max,
+                ..clock.clone()
+            };
+            return;
+        }
         if let Event::Reset = &event.event {
             self.state = ClockState::Stopped;
             self.last_state_change = event.timestamp;
@@ -42,6 +87,9 @@ impl ClockComponent {
         let Event::Clock(clock_event) = &event.event else {
             return;
         };
+        if self.check(clock_event, event.timestamp).is_err() {
+            return;
+        }
         match (&self.state, clock_event) {
             (_, E::Set(duration)) => {
                 self.last_state_change = event.timestamp;
@@ -99,14 +147,55 @@ impl ClockComponent {
             _ => {}
         }
     }
-    fn get_time_remaining(&self) -> Duration {
-        let time_elapsed = Instant::now() - self.last_state_change;
+    /// Moves a running clock captured at `taken_at` to `restored_at`, so it
+    /// carries on from where it was instead of counting the time in between.
+    pub fn rebase(&mut self, taken_at: Instant, restored_at: Instant) {
+        if let ClockState::Running = self.state {
+            self.last_time_remaining = self.get_time_remaining_at(taken_at);
+            self.last_state_change = restored_at;
+        }
+    }
+    pub fn get_time_remaining_at(&self, now: Instant) -> Duration {
+        let time_elapsed = now.saturating_duration_since(self.last_state_change);
         if matches!(self.state, ClockState::Running) {
             self.last_time_remaining.saturating_sub(time_elapsed)
         } else {
             self.last_time_remaining
         }
     }
+    pub fn get_data(&self, now: Instant) -> Value {
+        json!({
+            &self.name: {
+                "last_time_remaining": to_json_value(&self.last_time_remaining),
+                "last_state_change": to_json_value(&self.last_state_change),
+                "state": &self.state,
+                "time_remaining": format_time_remaining(self.get_time_remaining_at(now))
+            }
+        })
+    }
+}
+
+pub fn format_time_remaining(time_remaining: Duration) -> String {
+    let time_remaining = time_remaining.as_secs();
+    format!(
+        "{:0>2}:{:0>2}",
+        (time_remaining / 60) % 60,
+        time_remaining % 60
+    )
+}
+
+/// Game clock events that the shot clock mirrors onto itself.
+pub fn follows_game_clock(event: &LogEvent) -> bool {
+    matches!(
+        event,
+        LogEvent {
+            component: Component::Global(GlobalComponent::GameClock),
+            event: Event::Clock(
+                ClockEvent::Start(None) | ClockEvent::Stop(None) | ClockEvent::Expired
+            ),
+            ..
+        }
+    )
 }
 
 fn to_json_value<T: Milliseconds>(value: &T) -> Value {
@@ -122,16 +211,8 @@ fn start_data_channel_manager(
             let Ok(Value::Null) = data_channel.recv().await else {
                 continue;
             };
-            let clock = clock.data.lock().unwrap();
-            let time_remaining = clock.get_time_remaining().as_secs();
-            let _ = data_channel.send(json!({
-                &clock.name: {
-                    "last_time_remaining": to_json_value(&clock.last_time_remaining),
-                    "last_state_change": to_json_value(&clock.last_state_change),
-                    "state": &clock.state,
-                    "time_remaining": format!("{:0>2}:{:0>2}", (time_remaining / 60) % 60, time_remaining % 60)
-                }
-            }));
+            let data = clock.data.lock().unwrap().get_data(Instant::now());
+            let _ = data_channel.send(data);
         }
     });
 }
@@ -158,6 +239,7 @@ fn start_typed_data_channel_manager(
 #[derive(Debug)]
 pub struct GameClock {
     clock: Shareable<ClockComponent>,
+    horns: Horns,
     event_channel: MessageChannel<LogEvent>,
     data_channel: MessageChannel<Value>,
     typed_data_channel: MessageChannel<Option<(ClockState, Instant, Duration)>>,
@@ -167,19 +249,30 @@ impl GameClock {
         event_send: Sender<LogEvent>,
         data_log_send: Sender<Value>,
         typed_data_send: Sender<Option<(ClockState, Instant, Duration)>>,
+        horns: Horns,
+        max: Option<Duration>,
     ) -> Self {
         Self {
-            clock: ClockComponent::new("game_clock".into()).into(),
+            clock: ClockComponent::new("game_clock".into(), max).into(),
+            horns,
             event_channel: event_send.into(),
             data_channel: data_log_send.into(),
             typed_data_channel: typed_data_send.into(),
         }
     }
+    pub fn mirror(&self) -> ScoreboardComponent {
+        ScoreboardComponent::new(
+            Component::Global(GlobalComponent::GameClock),
+            ComponentState::Clock(self.clock.data.lock().unwrap().clone()),
+        )
+    }
     pub async fn run(mut self) {
         start_data_channel_manager(self.clock.clone(), self.data_channel);
+        let expiry = self.horns.expiry_cue(&self.clock.data.lock().unwrap().name);
         start_expiry_watcher(
             Component::Global(GlobalComponent::GameClock),
-            true,
+            expiry,
+            self.horns,
             self.event_channel.sender(),
             self.typed_data_channel.sender(),
         );
@@ -192,7 +285,11 @@ impl GameClock {
                 {
                     continue;
                 }
-                self.clock.data.lock().unwrap().process_event(&log_event);
+                let mut clock = self.clock.data.lock().unwrap();
+                if let Err(e) = clock.validate(&log_event) {
+                    warn!(component = ?log_event.component, error = %e, "ignoring out of bounds event");
+                }
+                clock.process_event(&log_event);
             }
         });
     }
@@ -200,7 +297,8 @@ impl GameClock {
 
 pub fn start_expiry_watcher(
     component: Component,
-    activate_siren: bool,
+    expiry: Option<HornCue>,
+    horns: Horns,
     event_sender: Sender<LogEvent>,
     clock_data_sender: Sender<Option<(ClockState, Instant, Duration)>>,
 ) {
@@ -220,26 +318,18 @@ pub fn start_expiry_watcher(
                 if time_elapsed < last_time_remaining {
                     break;
                 }
+                if horns.is_secondary() {
+                    break;
+                }
+                METRICS.observe_expiry_lateness(time_elapsed - last_time_remaining);
                 event_sender
                     .send(LogEvent::new_now(
                         component,
                         Event::Clock(ClockEvent::Expired),
                     ))
                     .unwrap();
-                if activate_siren {
-                    event_sender
-                        .send(LogEvent::new_now(
-                            Component::Global(GlobalComponent::Siren),
-                            Event::Toggle(ToggleEvent::Activate),
-                        ))
-                        .unwrap();
-                    sleep(Duration::from_secs(2)).await;
-                    event_sender
-                        .send(LogEvent::new_now(
-                            Component::Global(GlobalComponent::Siren),
-                            Event::Toggle(ToggleEvent::Deactivate),
-                        ))
-                        .unwrap();
+                if let Some(cue) = &expiry {
+                    horns.cue(cue);
                 }
                 break;
             }
@@ -252,6 +342,7 @@ pub fn start_expiry_watcher(
 pub struct GameDependentClock {
     component: Component,
     clock: Shareable<ClockComponent>,
+    horns: Horns,
     event_channel: MessageChannel<LogEvent>,
     data_channel: MessageChannel<Value>,
     typed_data_channel: MessageChannel<Option<(ClockState, Instant, Duration)>>,
@@ -263,20 +354,32 @@ impl GameDependentClock {
         component: Component,
         name: &str,
         typed_data_send: Sender<Option<(ClockState, Instant, Duration)>>,
+        horns: Horns,
+        max: Option<Duration>,
     ) -> Self {
         Self {
             component,
-            clock: ClockComponent::new(name.into()).into(),
+            clock: ClockComponent::new(name.into(), max).into(),
+            horns,
             event_channel: event_send.into(),
             data_channel: data_log_send.into(),
             typed_data_channel: typed_data_send.into(),
         }
     }
+    pub fn mirror(&self) -> ScoreboardComponent {
+        ScoreboardComponent::new(
+            self.component,
+            ComponentState::Clock(self.clock.data.lock().unwrap().clone()),
+        )
+        .following_game_clock()
+    }
     pub async fn run(mut self) {
         start_data_channel_manager(self.clock.clone(), self.data_channel);
+        let expiry = self.horns.expiry_cue(&self.clock.data.lock().unwrap().name);
         start_expiry_watcher(
             Component::Global(GlobalComponent::ShotClock),
-            false,
+            expiry,
+            self.horns,
             self.event_channel.sender(),
             self.typed_data_channel.sender(),
         );
@@ -284,22 +387,18 @@ impl GameDependentClock {
 
         tokio::spawn(async move {
             while let Ok(log_event) = self.event_channel.recv().await {
-                if !matches!(
-                    log_event,
-                    LogEvent {
-                        component: Component::Global(GlobalComponent::GameClock),
-                        event: Event::Clock(
-                            ClockEvent::Start(None) | ClockEvent::Stop(None) | ClockEvent::Expired
-                        ),
-                        ..
-                    }
-                ) && !self
-                    .component
-                    .is_event_component_relevant(&log_event.component)
+                if !follows_game_clock(&log_event)
+                    && !self
+                        .component
+                        .is_event_component_relevant(&log_event.component)
                 {
                     continue;
                 }
-                self.clock.data.lock().unwrap().process_event(&log_event);
+                let mut clock = self.clock.data.lock().unwrap();
+                if let Err(e) = clock.validate(&log_event) {
+                    warn!(component = ?log_event.component, error = %e, "ignoring out of bounds event");
+                }
+                clock.process_event(&log_event);
             }
         });
     }
@@ -309,6 +408,7 @@ impl GameDependentClock {
 pub struct StoppageClock {
     component: Component,
     clock: Shareable<ClockComponent>,
+    horns: Horns,
     event_channel: MessageChannel<LogEvent>,
     data_channel: MessageChannel<Value>,
     typed_data_channel: MessageChannel<Option<(ClockState, Instant, Duration)>>,
@@ -320,20 +420,31 @@ impl StoppageClock {
         component: Component,
         name: &str,
         typed_data_send: Sender<Option<(ClockState, Instant, Duration)>>,
+        horns: Horns,
+        max: Option<Duration>,
     ) -> Self {
         Self {
             component,
-            clock: ClockComponent::new(name.into()).into(),
+            clock: ClockComponent::new(name.into(), max).into(),
+            horns,
             event_channel: event_send.into(),
             data_channel: data_log_send.into(),
             typed_data_channel: typed_data_send.into(),
         }
     }
+    pub fn mirror(&self) -> ScoreboardComponent {
+        ScoreboardComponent::new(
+            self.component,
+            ComponentState::Clock(self.clock.data.lock().unwrap().clone()),
+        )
+    }
     pub async fn run(mut self) {
         start_data_channel_manager(self.clock.clone(), self.data_channel);
+        let expiry = self.horns.expiry_cue(&self.clock.data.lock().unwrap().name);
         start_expiry_watcher(
             self.component,
-            true,
+            expiry,
+            self.horns,
             self.event_channel.sender(),
             self.typed_data_channel.sender(),
         );
@@ -347,16 +458,11 @@ impl StoppageClock {
                 {
                     continue;
                 }
-                if let Event::Clock(ClockEvent::Start(_)) = log_event.event {
-                    self.event_channel
-                        .send(LogEvent {
-                            component: Component::Global(GlobalComponent::GameClock),
-                            event: Event::Clock(ClockEvent::Stop(None)),
-                            ..log_event
-                        })
-                        .expect("game clock stop message failed to send");
+                let mut clock = self.clock.data.lock().unwrap();
+                if let Err(e) = clock.validate(&log_event) {
+                    warn!(component = ?log_event.component, error = %e, "ignoring out of bounds event");
                 }
-                self.clock.data.lock().unwrap().process_event(&log_event);
+                clock.process_event(&log_event);
             }
         });
     }
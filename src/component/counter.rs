@@ -1,31 +1,105 @@
 use rocket::tokio::{self, sync::broadcast::Sender};
+use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
+use tracing::warn;
 
 use crate::{
     component::Component,
-    event::{
-        states::{CounterEvent, ToggleEvent},
-        Event, LogEvent, MessageChannel, Shareable,
-    },
+    event::{states::CounterEvent, Event, LogEvent, MessageChannel, Shareable},
+    scoreboard::{ComponentState, ScoreboardComponent},
 };
 
-use super::TeamComponent;
+/// Limits for a counter, set per counter name under `bounds.counters`.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CounterBounds {
+    pub min: u64,
+    pub max: u64,
+    /// How far `increment` and `decrement` move the counter.
+    pub step: u64,
+    /// Runs over from `max` to `min` and back instead of rejecting.
+    pub wrap: bool,
+}
+impl Default for CounterBounds {
+    fn default() -> Self {
+        Self {
+            min: 0,
+            max: u64::MAX,
+            step: 1,
+            wrap: false,
+        }
+    }
+}
+impl CounterBounds {
+    fn offset(&self, value: u64, delta: i128) -> Option<u64> {
+        let target = value as i128 + delta;
+        let (min, max) = (self.min as i128, self.max as i128);
+        if (min..=max).contains(&target) {
+            return Some(target as u64);
+        }
+        if !self.wrap || min > max {
+            return None;
+        }
+        Some((min + (target - min).rem_euclid(max - min + 1)) as u64)
+    }
+}
 
-#[derive(Debug, Clone)]
-struct InternalCounter {
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InternalCounter {
     orig_value: u64,
-    value: u64,
+    pub value: u64,
     name: String,
+    #[serde(skip)]
+    bounds: CounterBounds,
 }
 impl InternalCounter {
-    pub fn new(name: String, value: u64) -> Self {
+    pub fn new(name: String, value: u64, bounds: CounterBounds) -> Self {
         InternalCounter {
             value,
             orig_value: value,
             name,
+            bounds,
         }
     }
-    fn process_event(&mut self, event: &LogEvent) {
+    /// The value the event would leave the counter at, or why it can't.
+    pub fn apply(&self, event: &CounterEvent) -> Result<u64, String> {
+        let CounterBounds { min, max, step, .. } = self.bounds;
+        let delta = match *event {
+            CounterEvent::Set(value) if (min..=max).contains(&value) => return Ok(value),
+            CounterEvent::Set(value) => {
+                return Err(format!(
+                    "{} must be between {min} and {max}, not {value}",
+                    self.name
+                ))
+            }
+            CounterEvent::Increment => step.into(),
+            CounterEvent::Decrement => -i128::from(step),
+            event => event.delta().unwrap_or_default(),
+        };
+        self.bounds.offset(self.value, delta).ok_or_else(|| {
+            let limit = match delta < 0 {
+                true => format!("below {min}"),
+                false => format!("above {max}"),
+            };
+            format!("{} can't go {limit}, it is at {}", self.name, self.value)
+        })
+    }
+    /// Why `event` would be ignored for going out of bounds, if it would.
+    pub fn validate(&self, event: &LogEvent) -> Result<(), String> {
+        match &event.event {
+            Event::Counter(counter_event) => self.apply(counter_event).map(drop),
+            _ => Ok(()),
+        }
+    }
+    pub fn process_event(&mut self, event: &LogEvent) {
+        if let Event::Restore(ComponentState::Counter(counter)) = &event.event {
+            *self = InternalCounter {
+                name: std::mem::take(&mut self.name),
+                bounds: self.bounds,
+                ..counter.clone()
+            };
+            return;
+        }
         if let Event::Reset = &event.event {
             self.value = self.orig_value;
             return;
@@ -33,13 +107,13 @@ impl InternalCounter {
         let Event::Counter(counter_event) = &event.event else {
             return;
         };
-        use CounterEvent as E;
-        self.value = match *counter_event {
-            E::Increment => self.value + 1,
-            E::Decrement => self.value.saturating_sub(1),
-            E::Set(value) => value,
+        if let Ok(value) = self.apply(counter_event) {
+            self.value = value;
         }
     }
+    pub fn get_data(&self) -> Value {
+        json!({ &self.name: self.value })
+    }
 }
 
 #[derive(Debug)]
@@ -56,14 +130,21 @@ impl Counter {
         component: Component,
         name: &str,
         value: u64,
+        bounds: CounterBounds,
     ) -> Self {
         Self {
             component,
-            counter: InternalCounter::new(name.into(), value).into(),
+            counter: InternalCounter::new(name.into(), value, bounds).into(),
             event_channel: event_send.into(),
             data_channel: data_log_send.into(),
         }
     }
+    pub fn mirror(&self) -> ScoreboardComponent {
+        ScoreboardComponent::new(
+            self.component,
+            ComponentState::Counter(self.counter.data.lock().unwrap().clone()),
+        )
+    }
     pub async fn run(mut self) {
         let counter = self.counter.clone();
         tokio::spawn(async move {
@@ -71,10 +152,8 @@ impl Counter {
                 let Ok(Value::Null) = self.data_channel.recv().await else {
                     continue;
                 };
-                let counter = counter.data.lock().unwrap();
-                let _ = self
-                    .data_channel
-                    .send(json!({ counter.name.clone(): counter.value.clone() }));
+                let data = counter.data.lock().unwrap().get_data();
+                let _ = self.data_channel.send(data);
             }
         });
         tokio::spawn(async move {
@@ -85,81 +164,126 @@ impl Counter {
                 {
                     continue;
                 }
-                self.counter.data.lock().unwrap().process_event(&log_event);
+                let mut counter = self.counter.data.lock().unwrap();
+                if let Err(e) = counter.validate(&log_event) {
+                    warn!(component = ?log_event.component, error = %e, "ignoring out of bounds event");
+                }
+                counter.process_event(&log_event);
             }
         });
     }
 }
-#[derive(Debug)]
-pub struct TeamFoulCounter {
-    component: Component,
-    counter: Shareable<InternalCounter>,
-    event_channel: MessageChannel<LogEvent>,
-    data_channel: MessageChannel<Value>,
-}
-impl TeamFoulCounter {
-    pub fn new(
-        event_send: Sender<LogEvent>,
-        data_log_send: Sender<Value>,
-        component: Component,
-        name: &str,
-    ) -> Self {
-        Self {
-            component,
-            counter: InternalCounter::new(name.into(), 0).into(),
-            event_channel: event_send.into(),
-            data_channel: data_log_send.into(),
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::component::TeamComponent;
+
+    fn period(value: u64, bounds: CounterBounds) -> InternalCounter {
+        InternalCounter::new("period".into(), value, bounds)
+    }
+
+    fn periods(wrap: bool) -> CounterBounds {
+        CounterBounds {
+            min: 1,
+            max: 4,
+            step: 1,
+            wrap,
         }
     }
-    pub async fn run(mut self) {
-        let counter = self.counter.clone();
-        tokio::spawn(async move {
-            loop {
-                let Ok(Value::Null) = self.data_channel.recv().await else {
-                    continue;
-                };
-                let counter = counter.data.lock().unwrap();
-                let _ = self
-                    .data_channel
-                    .send(json!({ &counter.name: counter.value }));
-            }
-        });
-        tokio::spawn(async move {
-            while let Ok(log_event) = self.event_channel.recv().await {
-                if !self
-                    .component
-                    .is_event_component_relevant(&log_event.component)
-                {
-                    continue;
-                }
-                let mut counter = self.counter.data.lock().unwrap();
-                counter.process_event(&log_event);
 
-                let target = match self.component {
-                    Component::Away(_) => Component::Away(TeamComponent::TeamFoulWarning),
-                    Component::Home(_) => Component::Home(TeamComponent::TeamFoulWarning),
-                    _ => continue,
-                };
-                if counter.value > 5 && (counter.value + 1) % 5 == 0 {
-                    self.event_channel
-                        .send(LogEvent {
-                            component: target,
-                            event: Event::Toggle(ToggleEvent::Activate),
-                            ..log_event
-                        })
-                        .expect("message sent");
-                } else if counter.value > 5 && ((counter.value + 2) % 5 == 0)
-                    || ((counter.value) % 5 == 0)
-                {
-                    self.event_channel
-                        .send(LogEvent {
-                            component: target,
-                            event: Event::Toggle(ToggleEvent::Deactivate),
-                            ..log_event
-                        })
-                        .expect("message sent");
-                };
-            }
-        });
+    #[test]
+    fn rejects_leaving_the_bounds() {
+        let counter = period(4, periods(false));
+        assert_eq!(counter.apply(&CounterEvent::Decrement), Ok(3));
+        assert!(counter.apply(&CounterEvent::Increment).is_err());
+        assert!(counter.apply(&CounterEvent::Add(1)).is_err());
+        assert_eq!(counter.apply(&CounterEvent::Subtract(3)), Ok(1));
+        assert!(counter.apply(&CounterEvent::Subtract(4)).is_err());
+    }
+
+    #[test]
+    fn wraps_around_the_bounds() {
+        let counter = period(4, periods(true));
+        assert_eq!(counter.apply(&CounterEvent::Increment), Ok(1));
+        assert_eq!(counter.apply(&CounterEvent::Add(6)), Ok(2));
+        assert_eq!(counter.apply(&CounterEvent::Subtract(4)), Ok(4));
+        assert_eq!(counter.apply(&CounterEvent::Subtract(7)), Ok(1));
+        let counter = period(1, periods(true));
+        assert_eq!(counter.apply(&CounterEvent::Decrement), Ok(4));
+    }
+
+    #[test]
+    fn steps_by_the_configured_amount() {
+        let bounds = CounterBounds {
+            max: 10,
+            step: 3,
+            ..Default::default()
+        };
+        let counter = period(6, bounds);
+        assert_eq!(counter.apply(&CounterEvent::Increment), Ok(9));
+        assert_eq!(counter.apply(&CounterEvent::Decrement), Ok(3));
+        assert_eq!(
+            counter.apply(&CounterEvent::Add(1)),
+            Ok(7),
+            "add ignores step"
+        );
+        let counter = period(9, bounds);
+        assert!(counter.apply(&CounterEvent::Increment).is_err());
+    }
+
+    #[test]
+    fn sets_only_values_within_the_bounds() {
+        for wrap in [false, true] {
+            let counter = period(2, periods(wrap));
+            assert_eq!(counter.apply(&CounterEvent::Set(1)), Ok(1));
+            assert_eq!(counter.apply(&CounterEvent::Set(4)), Ok(4));
+            assert!(counter.apply(&CounterEvent::Set(0)).is_err());
+            assert!(counter.apply(&CounterEvent::Set(5)).is_err());
+        }
+    }
+
+    #[test]
+    fn handles_the_edges_of_the_range() {
+        let unbounded = CounterBounds::default();
+        let top = period(u64::MAX, unbounded);
+        assert!(top.apply(&CounterEvent::Increment).is_err());
+        assert!(top.apply(&CounterEvent::Add(u64::MAX)).is_err());
+        assert_eq!(top.apply(&CounterEvent::Subtract(u64::MAX)), Ok(0));
+        let bottom = period(0, unbounded);
+        assert!(bottom.apply(&CounterEvent::Decrement).is_err());
+        assert_eq!(bottom.apply(&CounterEvent::Add(u64::MAX)), Ok(u64::MAX));
+
+        let wrapping = CounterBounds {
+            wrap: true,
+            ..unbounded
+        };
+        let top = period(u64::MAX, wrapping);
+        assert_eq!(top.apply(&CounterEvent::Increment), Ok(0));
+        assert_eq!(top.apply(&CounterEvent::Add(u64::MAX)), Ok(u64::MAX - 1));
+        let bottom = period(0, wrapping);
+        assert_eq!(bottom.apply(&CounterEvent::Decrement), Ok(u64::MAX));
+
+        let inverted = CounterBounds {
+            min: 5,
+            max: 1,
+            wrap: true,
+            ..unbounded
+        };
+        assert!(period(3, inverted).apply(&CounterEvent::Increment).is_err());
+    }
+
+    #[test]
+    fn ignores_rejected_events() {
+        let mut counter = period(4, periods(false));
+        let component = Component::Home(TeamComponent::Score);
+        let event = LogEvent::new_now(component, Event::Counter(CounterEvent::Increment));
+        assert!(counter.validate(&event).is_err());
+        counter.process_event(&event);
+        assert_eq!(counter.value, 4);
+        let event = LogEvent::new_now(component, Event::Counter(CounterEvent::Set(2)));
+        assert!(counter.validate(&event).is_ok());
+        counter.process_event(&event);
+        assert_eq!(counter.value, 2);
     }
 }
@@ -1,26 +1,113 @@
-use rocket::tokio::{self, sync::broadcast::Sender};
-use serde::Serialize;
-use serde_json::{json, Value};
+use std::{collections::HashMap, time::Instant};
 
-use crate::event::{states::LabelEvent, Event, LogEvent, MessageChannel, Shareable};
+use rocket::tokio::{
+    self, select,
+    sync::broadcast::{error::RecvError, Sender},
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use tracing::warn;
 
-use super::Component;
+use crate::{
+    event::{states::LabelEvent, Event, LogEvent, MessageChannel, Shareable},
+    integration::flatten_data,
+    metrics::METRICS,
+    scoreboard::{ComponentState, Scoreboard, ScoreboardComponent},
+};
 
-#[derive(Debug, Clone, Serialize)]
-struct InternalLabel {
+use super::{BoundsConfig, Component};
+
+/// Characters a label may hold, e.g. to suit an LED board's font.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Charset {
+    /// Printable ASCII.
+    Ascii,
+    /// ASCII letters, digits and spaces.
+    Alphanumeric,
+    /// Exactly these characters.
+    Chars(String),
+}
+impl Charset {
+    fn allows(&self, c: char) -> bool {
+        match self {
+            Charset::Ascii => c.is_ascii_graphic() || c == ' ',
+            Charset::Alphanumeric => c.is_ascii_alphanumeric() || c == ' ',
+            Charset::Chars(chars) => chars.contains(c),
+        }
+    }
+}
+
+/// Limits for a label, set per label name under `bounds.labels`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LabelBounds {
+    pub max_length: Option<usize>,
+    pub charset: Option<Charset>,
+}
+impl LabelBounds {
+    /// `text` without the characters the charset leaves out, cut to the
+    /// maximum length, for text that can't just be rejected.
+    pub fn fit(&self, text: &str) -> String {
+        let charset = self.charset.as_ref();
+        text.chars()
+            .filter(|c| charset.is_none_or(|charset| charset.allows(*c)))
+            .take(self.max_length.unwrap_or(usize::MAX))
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InternalLabel {
     name: String,
     orig_value: String,
     value: String,
+    #[serde(skip)]
+    bounds: LabelBounds,
 }
 impl InternalLabel {
-    pub fn new(name: String, value: String) -> Self {
+    pub fn new(name: String, value: String, bounds: LabelBounds) -> Self {
         InternalLabel {
             name,
             orig_value: value.clone(),
             value,
+            bounds,
+        }
+    }
+    /// Why the label can't show `value`, if it can't.
+    pub fn check(&self, value: &str) -> Result<(), String> {
+        let length = value.chars().count();
+        if let Some(max_length) = self.bounds.max_length.filter(|max| length > *max) {
+            return Err(format!(
+                "{} can be at most {max_length} characters, not {length}",
+                self.name
+            ));
+        }
+        let charset = self.bounds.charset.as_ref();
+        match value
+            .chars()
+            .find(|c| charset.is_some_and(|charset| !charset.allows(*c)))
+        {
+            Some(c) => Err(format!("{} can't contain {c:?}", self.name)),
+            None => Ok(()),
         }
     }
-    fn process_event(&mut self, event: &LogEvent) {
+    /// Why `event` would be ignored for breaking the bounds, if it would.
+    pub fn validate(&self, event: &LogEvent) -> Result<(), String> {
+        match &event.event {
+            Event::Label(LabelEvent::Set(value)) => self.check(value),
+            _ => Ok(()),
+        }
+    }
+    pub fn process_event(&mut self, event: &LogEvent) {
+        if let Event::Restore(ComponentState::Label(label)) = &event.event {
+            *self = InternalLabel {
+                name: std::mem::take(&mut self.name),
+                bounds: std::mem::take(&mut self.bounds),
+                ..label.clone()
+            };
+            return;
+        }
         if let Event::Reset = &event.event {
             self.value.clone_from(&self.orig_value);
             return;
@@ -29,10 +116,17 @@ impl InternalLabel {
             return;
         };
         use LabelEvent as E;
-        self.value = match counter_event {
-            E::Set(value) => value.clone(),
+        match counter_event {
+            E::Set(value) if self.check(value).is_ok() => self.value.clone_from(value),
+            E::Set(_) => {}
         }
     }
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+    pub fn get_data(&self) -> Value {
+        json!({ &self.name: self.value })
+    }
 }
 
 #[derive(Debug)]
@@ -49,14 +143,21 @@ impl Label {
         component: Component,
         name: &str,
         value: &str,
+        bounds: LabelBounds,
     ) -> Self {
         Self {
             component,
-            label: InternalLabel::new(name.into(), value.into()).into(),
+            label: InternalLabel::new(name.into(), value.into(), bounds).into(),
             event_channel: event_send.into(),
             data_channel: data_log_send.into(),
         }
     }
+    pub fn mirror(&self) -> ScoreboardComponent {
+        ScoreboardComponent::new(
+            self.component,
+            ComponentState::Label(self.label.data.lock().unwrap().clone()),
+        )
+    }
     pub async fn run(mut self) {
         let label = self.label.clone();
         tokio::spawn(async move {
@@ -64,10 +165,8 @@ impl Label {
                 let Ok(Value::Null) = self.data_channel.recv().await else {
                     continue;
                 };
-                let label = label.data.lock().unwrap();
-                let _ = self.data_channel.send(json!({
-                    &label.name: label.value,
-                }));
+                let data = label.data.lock().unwrap().get_data();
+                let _ = self.data_channel.send(data);
             }
         });
         tokio::spawn(async move {
@@ -78,8 +177,180 @@ impl Label {
                 {
                     continue;
                 }
-                self.label.data.lock().unwrap().process_event(&log_event);
+                let mut label = self.label.data.lock().unwrap();
+                if let Err(e) = label.validate(&log_event) {
+                    warn!(component = ?log_event.component, error = %e, "ignoring out of bounds event");
+                }
+                label.process_event(&log_event);
             }
         });
     }
 }
+
+enum TemplatePart {
+    Text(String),
+    Field(String),
+}
+
+/// Text with `{placeholders}` naming flattened data keys, such as
+/// `{home_score}` or `{game_clock/time_remaining}`, or `{period_ordinal}`.
+pub struct LabelTemplate {
+    parts: Vec<TemplatePart>,
+}
+impl LabelTemplate {
+    pub fn parse(template: &str) -> Result<Self, String> {
+        let mut parts = vec![];
+        let mut rest = template;
+        while let Some(start) = rest.find('{') {
+            let Some(end) = rest[start..].find('}') else {
+                return Err(format!("unclosed `{{` in `{template}`"));
+            };
+            parts.push(TemplatePart::Text(rest[..start].into()));
+            parts.push(TemplatePart::Field(
+                rest[start + 1..start + end].trim().into(),
+            ));
+            rest = &rest[start + end + 1..];
+        }
+        parts.push(TemplatePart::Text(rest.into()));
+        Ok(Self { parts })
+    }
+    fn fields(&self) -> impl Iterator<Item = &str> {
+        self.parts.iter().filter_map(|part| match part {
+            TemplatePart::Field(field) => Some(field.as_str()),
+            TemplatePart::Text(_) => None,
+        })
+    }
+    pub fn render(&self, data: &Map<String, Value>) -> String {
+        let mut out = String::new();
+        for part in &self.parts {
+            match part {
+                TemplatePart::Text(text) => out.push_str(text),
+                TemplatePart::Field(field) => match data.get(field) {
+                    Some(Value::String(value)) => out.push_str(value),
+                    Some(Value::Null) | None => {}
+                    Some(value) => out.push_str(&value.to_string()),
+                },
+            }
+        }
+        out
+    }
+}
+
+/// `1st`, `2nd`, `3rd`, `4th`, ..., `11th`, `21st`.
+pub fn ordinal(n: u64) -> String {
+    let suffix = match (n % 10, n % 100) {
+        (_, 11..=13) => "th",
+        (1, _) => "st",
+        (2, _) => "nd",
+        (3, _) => "rd",
+        _ => "th",
+    };
+    format!("{n}{suffix}")
+}
+
+fn template_data(state: &Scoreboard, now: Instant) -> Map<String, Value> {
+    let mut data = flatten_data(&state.get_data(now));
+    if let Some(period) = data.get("period").and_then(Value::as_u64) {
+        data.insert("period_ordinal".into(), ordinal(period).into());
+    }
+    data
+}
+
+/// Serves labels computed from templates, keyed by name, e.g.
+/// `title = "{period_ordinal} Quarter"`. They follow a private copy of the
+/// scoreboard, so they're always up to date when data is requested.
+/// Rendered text is fitted to the label's `bounds.labels` entry.
+pub fn start_computed_labels(
+    templates: HashMap<String, String>,
+    bounds: &BoundsConfig,
+    mut state: Scoreboard,
+    event_sender: Sender<LogEvent>,
+    data_channel: Sender<Value>,
+) {
+    let known = template_data(&state, Instant::now());
+    let templates: Vec<_> = templates
+        .into_iter()
+        .map(|(name, template)| {
+            let invalid = |e: String| -> ! {
+                panic!("invalid `computed_labels` config: label `{name}`: {e}")
+            };
+            if known.contains_key(&name) {
+                invalid(format!("`{name}` is already in the data"));
+            }
+            let template = LabelTemplate::parse(&template).unwrap_or_else(|e| invalid(e));
+            if let Some(field) = template
+                .fields()
+                .find(|field| !known.contains_key(*field) && *field != "period_ordinal")
+            {
+                invalid(format!("unknown placeholder `{{{field}}}`"));
+            }
+            let bounds = bounds.label(&name);
+            (name, template, bounds)
+        })
+        .collect();
+
+    let mut events = event_sender.subscribe();
+    let mut requests = data_channel.subscribe();
+    tokio::spawn(async move {
+        loop {
+            // Events first, so labels reflect every event sent before the
+            // data was requested.
+            select! {
+                biased;
+                event = events.recv() => match event {
+                    Ok(event) => {
+                        state.apply_event(&event);
+                    }
+                    Err(RecvError::Lagged(skipped)) => {
+                        METRICS.record_lag("computed_labels", skipped)
+                    }
+                    Err(RecvError::Closed) => break,
+                },
+                request = requests.recv() => {
+                    let Ok(Value::Null) = request else {
+                        continue;
+                    };
+                    let data = template_data(&state, Instant::now());
+                    let labels: Map<_, _> = templates
+                        .iter()
+                        .map(|(name, template, bounds)| {
+                            (name.clone(), bounds.fit(&template.render(&data)).into())
+                        })
+                        .collect();
+                    let _ = data_channel.send(Value::Object(labels));
+                }
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn alphanumeric_is_ascii_only() {
+        let label = InternalLabel::new(
+            "home".into(),
+            String::new(),
+            LabelBounds {
+                max_length: None,
+                charset: Some(Charset::Alphanumeric),
+            },
+        );
+        assert!(label.check("Home 2").is_ok());
+        assert!(label.check("Köln").is_err());
+        assert!(label.check("٣").is_err());
+    }
+
+    #[test]
+    fn fits_text_to_bounds() {
+        let bounds = LabelBounds {
+            max_length: Some(6),
+            charset: Some(Charset::Alphanumeric),
+        };
+        assert_eq!(bounds.fit("1st Quarter"), "1st Qu");
+        assert_eq!(bounds.fit("Köln-Süd"), "KlnSd");
+        assert_eq!(LabelBounds::default().fit("Köln-Süd"), "Köln-Süd");
+    }
+}
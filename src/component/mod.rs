@@ -1,9 +1,15 @@
+use std::{collections::HashMap, time::Duration};
+
+use counter::CounterBounds;
+use label::LabelBounds;
 use rocket::request::FromParam;
+use serde::{Deserialize, Serialize};
 use strum::{EnumString, ParseError};
 
 pub mod clock;
 pub mod counter;
 pub mod label;
+pub mod possession;
 pub mod toggle;
 
 macro_rules! generate_components {
@@ -13,13 +19,14 @@ macro_rules! generate_components {
             counter: $(- $g_counter_name: ident)*
             toggle: $(- $g_toggle_name: ident)*
             label: $(- $g_label_name: ident)*
+            possession: $(- $g_possession_name: ident)*
         per_team:
             clock: $(- $t_clock_name: ident)*
             counter: $(- $t_counter_name: ident)*
             toggle: $(- $t_toggle_name: ident)*
             label: $(- $t_label_name: ident)*
     ) => {
-            #[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+            #[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, Serialize, Deserialize)]
             pub enum Component {
                 All,
                 Global(GlobalComponent),
@@ -55,14 +62,21 @@ macro_rules! generate_components {
                         _ => false,
                     }
                 }
+                pub fn is_possession(&self) -> bool {
+                    match self {
+                        Component::Global(c) => c.is_possession(),
+                        _ => false,
+                    }
+                }
             }
-            #[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, EnumString)]
+            #[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, EnumString, Serialize, Deserialize)]
             #[strum(ascii_case_insensitive)]
             pub enum GlobalComponent {
                 $($g_clock_name ,)*
                 $($g_counter_name ,)*
                 $($g_toggle_name ,)*
                 $($g_label_name ,)*
+                $($g_possession_name ,)*
             }
             impl GlobalComponent {
                 pub fn is_clock(&self) -> bool {
@@ -77,8 +91,11 @@ macro_rules! generate_components {
                 pub fn is_label(&self) -> bool {
                     $(matches!(self, Self::$g_label_name))||*
                 }
+                pub fn is_possession(&self) -> bool {
+                    $(matches!(self, Self::$g_possession_name))||*
+                }
             }
-            #[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, EnumString)]
+            #[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, EnumString, Serialize, Deserialize)]
             #[strum(ascii_case_insensitive)]
             pub enum TeamComponent {
                 $($t_clock_name ,)*
@@ -113,8 +130,11 @@ generate_components!(
             - Period
         toggle:
             - Siren
+            - ShotClockHorn
         label:
             - MatchTitle
+        possession:
+            - Possession
     per_team:
         clock:
             - InferiorityClock
@@ -126,12 +146,64 @@ generate_components!(
             - TeamFoulWarning
         label:
             - TeamName
+            - Logo
 );
 
 impl Component {
-    fn is_event_component_relevant(&self, event_component: &Component) -> bool {
+    pub fn is_event_component_relevant(&self, event_component: &Component) -> bool {
         self == event_component || event_component == &Component::All
     }
+    /// Parses the route style of addressing components, e.g. `home/score` or `global/period`.
+    pub fn from_path(path: &str) -> Option<Self> {
+        let path = path.trim_matches('/');
+        if path.eq_ignore_ascii_case("all") {
+            return Some(Component::All);
+        }
+        let (scope, name) = path.split_once('/')?;
+        match scope.to_ascii_lowercase().as_str() {
+            "global" => name.parse().ok().map(Component::Global),
+            "home" => name.parse().ok().map(Component::Home),
+            "away" => name.parse().ok().map(Component::Away),
+            _ => None,
+        }
+    }
+    /// The inverse of [`Component::from_path`], e.g. `home/teamfouls`.
+    pub fn path(&self) -> String {
+        match self {
+            Component::All => "all".into(),
+            Component::Global(c) => format!("global/{c:?}").to_ascii_lowercase(),
+            Component::Home(c) => format!("home/{c:?}").to_ascii_lowercase(),
+            Component::Away(c) => format!("away/{c:?}").to_ascii_lowercase(),
+        }
+    }
+}
+
+/// The `bounds` section of the Rocket config, keyed by data name such as
+/// `period`, `home_score`, `shot_clock` or `home`.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct BoundsConfig {
+    pub counters: HashMap<String, CounterBounds>,
+    pub clocks: HashMap<String, ClockBounds>,
+    pub labels: HashMap<String, LabelBounds>,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct ClockBounds {
+    pub max_ms: Option<u64>,
+}
+
+impl BoundsConfig {
+    pub fn counter(&self, name: &str) -> CounterBounds {
+        self.counters.get(name).copied().unwrap_or_default()
+    }
+    pub fn clock(&self, name: &str) -> Option<Duration> {
+        let bounds = self.clocks.get(name)?;
+        bounds.max_ms.map(Duration::from_millis)
+    }
+    pub fn label(&self, name: &str) -> LabelBounds {
+        self.labels.get(name).cloned().unwrap_or_default()
+    }
 }
 
 impl<'a> FromParam<'a> for TeamComponent {
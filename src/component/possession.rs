@@ -0,0 +1,142 @@
+use std::time::Duration;
+
+use rocket::tokio::{self, sync::broadcast::Sender};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tracing::error;
+
+use crate::{
+    component::Component,
+    event::{
+        states::{ClockEvent, PossessionEvent, PossessionState},
+        Event, LogEvent, MessageChannel, Shareable,
+    },
+    replication::Replication,
+    scoreboard::{ComponentState, ScoreboardComponent},
+};
+
+use super::GlobalComponent;
+
+/// The `possession` section of the Rocket config.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct PossessionConfig {
+    /// Sets the shot clock to this whenever the ball changes hands.
+    pub shot_clock_reset_ms: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InternalPossession {
+    pub state: PossessionState,
+    name: String,
+}
+impl InternalPossession {
+    pub fn new(name: String) -> Self {
+        InternalPossession {
+            state: PossessionState::None,
+            name,
+        }
+    }
+    pub fn process_event(&mut self, event: &LogEvent) {
+        if let Event::Restore(ComponentState::Possession(possession)) = &event.event {
+            self.state = possession.state;
+            return;
+        }
+        if let Event::Reset = &event.event {
+            self.state = PossessionState::None;
+            return;
+        }
+        let Event::Possession(possession_event) = &event.event else {
+            return;
+        };
+        use PossessionEvent as E;
+        use PossessionState as S;
+        self.state = match (*possession_event, self.state) {
+            (E::Home, _) | (E::Flip, S::Away) => S::Home,
+            (E::Away, _) | (E::Flip, S::Home) => S::Away,
+            (E::Clear, _) | (E::Flip, S::None) => S::None,
+        }
+    }
+    pub fn get_data(&self) -> Value {
+        json!({ &self.name: self.state })
+    }
+}
+
+#[derive(Debug)]
+pub struct Possession {
+    component: Component,
+    possession: Shareable<InternalPossession>,
+    shot_clock_reset: Option<Duration>,
+    replication: Replication,
+    event_channel: MessageChannel<LogEvent>,
+    data_channel: MessageChannel<Value>,
+}
+impl Possession {
+    pub fn new(
+        event_send: Sender<LogEvent>,
+        data_log_send: Sender<Value>,
+        component: Component,
+        name: &str,
+        config: &PossessionConfig,
+        replication: Replication,
+    ) -> Self {
+        Self {
+            component,
+            possession: InternalPossession::new(name.into()).into(),
+            shot_clock_reset: config.shot_clock_reset_ms.map(Duration::from_millis),
+            replication,
+            event_channel: event_send.into(),
+            data_channel: data_log_send.into(),
+        }
+    }
+    pub fn mirror(&self) -> ScoreboardComponent {
+        ScoreboardComponent::new(
+            self.component,
+            ComponentState::Possession(self.possession.data.lock().unwrap().clone()),
+        )
+    }
+    pub async fn run(mut self) {
+        let possession = self.possession.clone();
+        tokio::spawn(async move {
+            loop {
+                let Ok(Value::Null) = self.data_channel.recv().await else {
+                    continue;
+                };
+                let data = possession.data.lock().unwrap().get_data();
+                let _ = self.data_channel.send(data);
+            }
+        });
+        tokio::spawn(async move {
+            while let Ok(log_event) = self.event_channel.recv().await {
+                if !self
+                    .component
+                    .is_event_component_relevant(&log_event.component)
+                {
+                    continue;
+                }
+                let mut possession = self.possession.data.lock().unwrap();
+                let before = possession.state;
+                possession.process_event(&log_event);
+                if !matches!(log_event.event, Event::Possession(_)) {
+                    continue;
+                }
+                let changed_hands =
+                    before != possession.state && possession.state != PossessionState::None;
+                let Some(reset) = self.shot_clock_reset.filter(|_| changed_hands) else {
+                    continue;
+                };
+                // Secondaries get the reset from their primary.
+                if self.replication.is_secondary() {
+                    continue;
+                }
+                let event = log_event.derive(
+                    Component::Global(GlobalComponent::ShotClock),
+                    Event::Clock(ClockEvent::Set(reset)),
+                );
+                if let Err(e) = self.event_channel.send(event) {
+                    error!(error = %e, "possession: failed to send shot clock reset");
+                }
+            }
+        });
+    }
+}
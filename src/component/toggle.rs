@@ -1,4 +1,5 @@
 use rocket::tokio::{self, sync::broadcast::Sender};
+use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
 
 use crate::{
@@ -7,12 +8,13 @@ use crate::{
         states::{ToggleEvent, ToggleState},
         Event, LogEvent, MessageChannel, Shareable,
     },
+    scoreboard::{ComponentState, ScoreboardComponent},
 };
 
 use super::GlobalComponent;
 
-#[derive(Debug, Clone)]
-struct InteralToggle {
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InteralToggle {
     state: ToggleState,
     name: String,
 }
@@ -23,7 +25,14 @@ impl InteralToggle {
             name,
         }
     }
-    fn process_event(&mut self, event: &LogEvent) {
+    pub fn is_active(&self) -> bool {
+        matches!(self.state, ToggleState::Active)
+    }
+    pub fn process_event(&mut self, event: &LogEvent) {
+        if let Event::Restore(ComponentState::Toggle(toggle)) = &event.event {
+            self.state = toggle.state;
+            return;
+        }
         if let Event::Reset = &event.event {
             self.state = ToggleState::Inactive;
             return;
@@ -37,6 +46,9 @@ impl InteralToggle {
             E::Deactivate => ToggleState::Inactive,
         }
     }
+    pub fn get_data(&self) -> Value {
+        json!({ &self.name: self.is_active() })
+    }
 }
 
 #[derive(Debug)]
@@ -60,6 +72,12 @@ impl Toggle {
             data_channel: data_log_send.into(),
         }
     }
+    pub fn mirror(&self) -> ScoreboardComponent {
+        ScoreboardComponent::new(
+            self.component,
+            ComponentState::Toggle(self.toggle.data.lock().unwrap().clone()),
+        )
+    }
     pub async fn run(mut self) {
         let toggle = self.toggle.clone();
         tokio::spawn(async move {
@@ -67,10 +85,8 @@ impl Toggle {
                 let Ok(Value::Null) = self.data_channel.recv().await else {
                     continue;
                 };
-                let toggle = toggle.data.lock().unwrap();
-                let _ = self.data_channel.send(json!({
-                    &toggle.name: matches!(toggle.state, ToggleState::Active)
-                }));
+                let data = toggle.data.lock().unwrap().get_data();
+                let _ = self.data_channel.send(data);
             }
         });
         tokio::spawn(async move {
@@ -101,6 +117,12 @@ impl Siren {
             data_channel: data_log_send.into(),
         }
     }
+    pub fn mirror(&self) -> ScoreboardComponent {
+        ScoreboardComponent::new(
+            Component::Global(GlobalComponent::Siren),
+            ComponentState::Toggle(self.state.data.lock().unwrap().clone()),
+        )
+    }
     pub async fn run(mut self) {
         let toggle = self.state.clone();
         tokio::spawn(async move {
@@ -108,10 +130,8 @@ impl Siren {
                 let Ok(Value::Null) = self.data_channel.recv().await else {
                     continue;
                 };
-                let toggle = toggle.data.lock().unwrap();
-                let _ = self.data_channel.send(json!({
-                    &toggle.name: matches!(toggle.state, ToggleState::Active)
-                }));
+                let data = toggle.data.lock().unwrap().get_data();
+                let _ = self.data_channel.send(data);
             }
         });
         tokio::spawn(async move {
@@ -0,0 +1,281 @@
+use std::{
+    collections::BTreeMap,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use rocket::tokio::{self, sync::broadcast::Sender};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tracing::error;
+
+use crate::event::{DataRefresh, Shareable};
+
+/// Which fields a display shows, keyed by data name, e.g.
+/// `shot_clock = false` or `home_inferiority_clock = true`. Fields left out
+/// are up to the display.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Layout {
+    pub visible: BTreeMap<String, bool>,
+}
+
+/// Colours and fonts by role, e.g. `background = "#101820"` or
+/// `clock = "DSEG7 Classic"`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Theme {
+    pub colors: BTreeMap<String, String>,
+    pub fonts: BTreeMap<String, String>,
+}
+impl Theme {
+    /// Colours must be `#rgb`, `#rgba`, `#rrggbb` or `#rrggbbaa`.
+    fn check(&self) -> Result<(), String> {
+        for (role, color) in &self.colors {
+            let hex = color.strip_prefix('#').unwrap_or_default();
+            let valid =
+                matches!(hex.len(), 3 | 4 | 6 | 8) && hex.chars().all(|c| c.is_ascii_hexdigit());
+            if !valid {
+                return Err(format!(
+                    "{role} colour must be a hex colour like #1a2b3c, not {color:?}"
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The `display` section of the Rocket config.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct DisplayConfig {
+    /// Where layouts and themes edited at runtime are kept. These win over
+    /// the ones of the same name below.
+    pub file: PathBuf,
+    pub layouts: BTreeMap<String, Layout>,
+    pub themes: BTreeMap<String, Theme>,
+    /// Active at startup unless another was activated since.
+    pub layout: String,
+    pub theme: String,
+}
+impl Default for DisplayConfig {
+    fn default() -> Self {
+        Self {
+            file: "display.json".into(),
+            layouts: BTreeMap::new(),
+            themes: BTreeMap::new(),
+            layout: "default".into(),
+            theme: "default".into(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct Saved {
+    layouts: BTreeMap<String, Layout>,
+    themes: BTreeMap<String, Theme>,
+    layout: Option<String>,
+    theme: Option<String>,
+}
+
+/// Named layouts and themes, and the one of each that is active.
+#[derive(Debug, Clone)]
+struct DisplayState {
+    layouts: BTreeMap<String, Layout>,
+    themes: BTreeMap<String, Theme>,
+    layout: String,
+    theme: String,
+}
+impl DisplayState {
+    fn active(&self) -> ActiveDisplay {
+        ActiveDisplay {
+            layout: self.layout.clone(),
+            theme: self.theme.clone(),
+            layout_settings: self.layouts[&self.layout].clone(),
+            theme_settings: self.themes[&self.theme].clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ActiveDisplay {
+    pub layout: String,
+    pub theme: String,
+    #[serde(flatten)]
+    pub layout_settings: Layout,
+    #[serde(flatten)]
+    pub theme_settings: Theme,
+}
+
+/// Why a layout or theme couldn't be changed.
+#[derive(Debug)]
+pub enum DisplayError {
+    NotFound,
+    Active,
+    Invalid(String),
+    Io(io::Error),
+}
+
+#[derive(Debug, Clone)]
+pub struct DisplaySettings {
+    state: Shareable<DisplayState>,
+    file: PathBuf,
+    refresh: DataRefresh,
+}
+impl DisplaySettings {
+    pub fn active(&self) -> ActiveDisplay {
+        self.state.data.lock().unwrap().active()
+    }
+    pub fn layouts(&self) -> BTreeMap<String, Layout> {
+        self.state.data.lock().unwrap().layouts.clone()
+    }
+    pub fn themes(&self) -> BTreeMap<String, Theme> {
+        self.state.data.lock().unwrap().themes.clone()
+    }
+    pub fn set_layout(&self, name: &str, layout: Layout) -> Result<(), DisplayError> {
+        self.update(|state| {
+            state.layouts.insert(name.into(), layout);
+            Ok(())
+        })
+    }
+    pub fn set_theme(&self, name: &str, theme: Theme) -> Result<(), DisplayError> {
+        theme.check().map_err(DisplayError::Invalid)?;
+        self.update(|state| {
+            state.themes.insert(name.into(), theme);
+            Ok(())
+        })
+    }
+    /// The active layout can't be deleted.
+    pub fn delete_layout(&self, name: &str) -> Result<(), DisplayError> {
+        self.update(|state| match state.layouts.contains_key(name) {
+            false => Err(DisplayError::NotFound),
+            true if state.layout == name => Err(DisplayError::Active),
+            true => {
+                state.layouts.remove(name);
+                Ok(())
+            }
+        })
+    }
+    /// The active theme can't be deleted.
+    pub fn delete_theme(&self, name: &str) -> Result<(), DisplayError> {
+        self.update(|state| match state.themes.contains_key(name) {
+            false => Err(DisplayError::NotFound),
+            true if state.theme == name => Err(DisplayError::Active),
+            true => {
+                state.themes.remove(name);
+                Ok(())
+            }
+        })
+    }
+    pub fn activate_layout(&self, name: &str) -> Result<(), DisplayError> {
+        self.update(|state| match state.layouts.contains_key(name) {
+            false => Err(DisplayError::NotFound),
+            true => {
+                state.layout = name.into();
+                Ok(())
+            }
+        })
+    }
+    pub fn activate_theme(&self, name: &str) -> Result<(), DisplayError> {
+        self.update(|state| match state.themes.contains_key(name) {
+            false => Err(DisplayError::NotFound),
+            true => {
+                state.theme = name.into();
+                Ok(())
+            }
+        })
+    }
+    /// Applies `change` and saves the result, leaving the state as it was if
+    /// either fails. Data streams are refreshed if the active display
+    /// changed.
+    fn update(
+        &self,
+        change: impl FnOnce(&mut DisplayState) -> Result<(), DisplayError>,
+    ) -> Result<(), DisplayError> {
+        let mut state = self.state.data.lock().unwrap();
+        let mut changed = state.clone();
+        change(&mut changed)?;
+        let saved = Saved {
+            layouts: changed.layouts.clone(),
+            themes: changed.themes.clone(),
+            layout: Some(changed.layout.clone()),
+            theme: Some(changed.theme.clone()),
+        };
+        let json = serde_json::to_string_pretty(&saved).expect("display settings serialize");
+        fs::write(&self.file, json).map_err(DisplayError::Io)?;
+        let restyled = changed.active() != state.active();
+        *state = changed;
+        if restyled {
+            self.refresh.notify();
+        }
+        Ok(())
+    }
+}
+
+fn load_saved(path: &Path) -> Saved {
+    let Ok(saved) = fs::read_to_string(path) else {
+        return Saved::default();
+    };
+    serde_json::from_str(&saved).unwrap_or_else(|e| {
+        error!(path = %path.display(), error = %e, "display: ignoring invalid settings");
+        Saved::default()
+    })
+}
+
+/// Serves the active layout and theme in the data as `display`, refreshing
+/// data streams so displays restyle as soon as either changes.
+pub fn start_display(
+    config: DisplayConfig,
+    data_channel: Sender<Value>,
+    refresh: DataRefresh,
+) -> DisplaySettings {
+    let saved = load_saved(&config.file);
+    let mut layouts = BTreeMap::from([("default".to_string(), Layout::default())]);
+    layouts.extend(config.layouts);
+    layouts.extend(saved.layouts);
+    let mut themes = BTreeMap::from([("default".to_string(), Theme::default())]);
+    themes.extend(config.themes);
+    themes.extend(saved.themes);
+    for (name, theme) in &themes {
+        if let Err(e) = theme.check() {
+            panic!("invalid `display` config: theme `{name}`: {e}");
+        }
+    }
+    let layout = saved
+        .layout
+        .filter(|name| layouts.contains_key(name))
+        .unwrap_or(config.layout);
+    let theme = saved
+        .theme
+        .filter(|name| themes.contains_key(name))
+        .unwrap_or(config.theme);
+    if !layouts.contains_key(&layout) {
+        panic!("invalid `display` config: unknown layout `{layout}`");
+    }
+    if !themes.contains_key(&theme) {
+        panic!("invalid `display` config: unknown theme `{theme}`");
+    }
+    let display = DisplaySettings {
+        state: DisplayState {
+            layouts,
+            themes,
+            layout,
+            theme,
+        }
+        .into(),
+        file: config.file,
+        refresh,
+    };
+
+    let data_display = display.clone();
+    let mut data_recv = data_channel.subscribe();
+    tokio::spawn(async move {
+        loop {
+            let Ok(Value::Null) = data_recv.recv().await else {
+                continue;
+            };
+            let _ = data_channel.send(json!({ "display": data_display.active() }));
+        }
+    });
+    display
+}
@@ -7,29 +7,139 @@ use std::{
 };
 
 use rocket::tokio::sync::broadcast::{
+    self,
     error::{RecvError, SendError},
     Receiver, Sender,
 };
-use states::{ClockEvent, CounterEvent, LabelEvent, ToggleEvent};
+use serde::{Deserialize, Serialize};
+use states::{ClockEvent, CounterEvent, LabelEvent, PossessionEvent, ToggleEvent};
+use strum::AsRefStr;
 use uuid::Uuid;
 
-use crate::component::Component;
+use crate::{component::Component, scoreboard::ComponentState};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize, AsRefStr)]
 pub enum Event {
     Clock(ClockEvent),
     Counter(CounterEvent),
     Toggle(ToggleEvent),
     Label(LabelEvent),
+    Possession(PossessionEvent),
     Reset,
+    /// Replaces the component's state wholesale, e.g. from a snapshot.
+    Restore(ComponentState),
+}
+impl Event {
+    /// Name of the specific event, e.g. `Increment` for `Counter(Increment)`.
+    pub fn action(&self) -> &str {
+        match self {
+            Event::Clock(event) => event.as_ref(),
+            Event::Counter(event) => event.as_ref(),
+            Event::Toggle(event) => event.as_ref(),
+            Event::Label(event) => event.as_ref(),
+            Event::Possession(event) => event.as_ref(),
+            Event::Reset | Event::Restore(_) => self.as_ref(),
+        }
+    }
 }
 
-#[derive(Debug, Clone)]
+impl Event {
+    /// Builds an event from the route style action name and `?value=`
+    /// parameter, choosing the event type from the targeted component.
+    pub fn parse(component: &Component, action: &str, value: Option<&str>) -> Option<Self> {
+        // `None` if given but not a whole number, `Some(None)` if left out.
+        let number = || {
+            value
+                .map(|value| value.trim().parse::<u64>())
+                .transpose()
+                .ok()
+        };
+        if matches!(component, Component::All) {
+            return action.eq_ignore_ascii_case("reset").then_some(Event::Reset);
+        }
+        if component.is_clock() {
+            let event: ClockEvent = action.parse().ok()?;
+            Some(Event::Clock(event.with_value(number()?)))
+        } else if component.is_counter() {
+            let event: CounterEvent = action.parse().ok()?;
+            Some(Event::Counter(event.with_value(number()?)))
+        } else if component.is_toggle() {
+            Some(Event::Toggle(action.parse().ok()?))
+        } else if component.is_label() {
+            let event: LabelEvent = action.parse().ok()?;
+            Some(Event::Label(event.with_value(value.map(String::from))))
+        } else if component.is_possession() {
+            Some(Event::Possession(action.parse().ok()?))
+        } else {
+            None
+        }
+    }
+}
+
+/// Parses a command such as `home/score/increment`, `global/gameclock/set`
+/// or `reset` into the component and event it addresses.
+pub fn parse_command(path: &str, value: Option<&str>) -> Option<(Component, Event)> {
+    let path = path.trim_matches('/');
+    let (component, action) = match path.rsplit_once('/') {
+        Some((component, action)) => (Component::from_path(component)?, action),
+        None => (Component::All, path),
+    };
+    let event = Event::parse(&component, action, value)?;
+    Some((component, event))
+}
+
+/// Matches events by route style component path (`home/score`) and by
+/// event type (`counter`) or specific event (`increment`).
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct EventPattern {
+    pub component: Option<String>,
+    pub kind: Option<String>,
+}
+impl EventPattern {
+    pub fn matches(&self, event: &LogEvent) -> bool {
+        event_matches(self.component.as_deref(), self.kind.as_deref(), event)
+    }
+}
+
+pub fn event_matches(component: Option<&str>, kind: Option<&str>, event: &LogEvent) -> bool {
+    if let Some(component) = component {
+        if Component::from_path(component) != Some(event.component) {
+            return false;
+        }
+    }
+    kind.is_none_or(|kind| {
+        kind.eq_ignore_ascii_case(event.event.as_ref())
+            || kind.eq_ignore_ascii_case(event.event.action())
+    })
+}
+
+/// Who caused an event to be sent.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EventSource {
+    #[default]
+    Operator,
+    System,
+    Integration(String),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LogEvent {
+    #[serde(with = "serde_millis")]
     pub timestamp: Instant,
     pub log_id: Uuid,
     pub component: Component,
     pub event: Event,
+    #[serde(default)]
+    pub source: EventSource,
+    /// What kind of score a counter event records, e.g. `3pt` or `try`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub score_type: Option<String>,
+    /// How many events this one was derived through, 0 for an original.
+    #[serde(default, skip_serializing_if = "is_original")]
+    pub depth: u32,
+}
+fn is_original(depth: &u32) -> bool {
+    *depth == 0
 }
 impl LogEvent {
     pub fn new_now(component: Component, event: Event) -> Self {
@@ -38,6 +148,9 @@ impl LogEvent {
             log_id: Uuid::new_v4(),
             component,
             event,
+            source: EventSource::System,
+            score_type: None,
+            depth: 0,
         }
     }
     pub fn new(
@@ -47,11 +160,7 @@ impl LogEvent {
         uuid: Option<String>,
     ) -> Self {
         let timestamp = ts
-            .and_then(|ts| {
-                let ts_str = ts.to_string();
-                let mut deserializer = serde_json::Deserializer::from_str(&ts_str);
-                serde_millis::deserialize(&mut deserializer).ok()
-            })
+            .and_then(|ts| instant_from_millis(ts as u64))
             .unwrap_or_else(Instant::now);
 
         let log_id = uuid
@@ -62,10 +171,32 @@ impl LogEvent {
             log_id,
             component,
             event,
+            source: EventSource::Operator,
+            score_type: None,
+            depth: 0,
+        }
+    }
+    /// An event emitted by a component in response to this one.
+    pub fn derive(&self, component: Component, event: Event) -> Self {
+        Self {
+            log_id: Uuid::new_v4(),
+            component,
+            event,
+            source: EventSource::System,
+            score_type: None,
+            depth: self.depth + 1,
+            ..self.clone()
         }
     }
 }
 
+/// Converts milliseconds since the Unix epoch into an `Instant`.
+pub fn instant_from_millis(ms: u64) -> Option<Instant> {
+    let ms_str = ms.to_string();
+    let mut deserializer = serde_json::Deserializer::from_str(&ms_str);
+    serde_millis::deserialize(&mut deserializer).ok()
+}
+
 #[derive(Debug)]
 pub struct MessageChannel<T: Clone> {
     send: Sender<T>,
@@ -93,6 +224,28 @@ impl<T: Clone> MessageChannel<T> {
     }
 }
 
+/// Tells data streams to resend the data when it changes without an event,
+/// e.g. when the ticker moves on.
+#[derive(Debug, Clone)]
+pub struct DataRefresh {
+    send: Sender<()>,
+}
+impl Default for DataRefresh {
+    fn default() -> Self {
+        Self {
+            send: broadcast::channel(16).0,
+        }
+    }
+}
+impl DataRefresh {
+    pub fn notify(&self) {
+        let _ = self.send.send(());
+    }
+    pub fn subscribe(&self) -> Receiver<()> {
+        self.send.subscribe()
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Shareable<T> {
     pub data: Arc<Mutex<T>>,
@@ -104,3 +257,40 @@ impl<T> From<T> for Shareable<T> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::component::TeamComponent;
+
+    #[test]
+    fn parses_values_as_whole_numbers() {
+        let score = Component::Home(TeamComponent::Score);
+        let parse = |value| Event::parse(&score, "add", Some(value));
+        assert!(matches!(
+            parse("18446744073709551615"),
+            Some(Event::Counter(CounterEvent::Add(u64::MAX)))
+        ));
+        assert!(matches!(
+            parse(" 9007199254740993 "),
+            Some(Event::Counter(CounterEvent::Add(9_007_199_254_740_993)))
+        ));
+        for invalid in ["18446744073709551616", "1.5", "-1", "1e3", "inf", ""] {
+            assert!(parse(invalid).is_none(), "{invalid}");
+        }
+        assert!(matches!(
+            Event::parse(&score, "add", None),
+            Some(Event::Counter(CounterEvent::Add(1)))
+        ));
+    }
+
+    #[test]
+    fn counter_deltas_cover_the_whole_range() {
+        assert_eq!(CounterEvent::Add(u64::MAX).delta(), Some(u64::MAX as i128));
+        assert_eq!(
+            CounterEvent::Subtract(u64::MAX).delta(),
+            Some(-(u64::MAX as i128))
+        );
+        assert_eq!(CounterEvent::Set(u64::MAX).delta(), None);
+    }
+}
@@ -2,7 +2,7 @@ use std::time::Duration;
 
 use rocket::request::FromParam;
 use serde::{Deserialize, Serialize};
-use strum::{EnumString, ParseError};
+use strum::{AsRefStr, EnumString, ParseError};
 
 #[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub enum ClockState {
@@ -10,7 +10,7 @@ pub enum ClockState {
     Running,
 }
 
-#[derive(Debug, Clone, Copy, Serialize, EnumString)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, EnumString, AsRefStr)]
 #[strum(ascii_case_insensitive)]
 pub enum ClockEvent {
     Set(Duration),
@@ -20,6 +20,23 @@ pub enum ClockEvent {
     Decrement(Duration),
     Expired,
 }
+impl ClockEvent {
+    /// Fills in the duration passed as `?value=` in milliseconds.
+    pub fn with_value(self, value: Option<u64>) -> Self {
+        let Some(ms) = value else {
+            return self;
+        };
+        let duration = Duration::from_millis(ms);
+        match self {
+            Self::Set(_) => Self::Set(duration),
+            Self::Increment(_) => Self::Increment(duration),
+            Self::Decrement(_) => Self::Decrement(duration),
+            Self::Start(_) => Self::Start(Some(duration)),
+            Self::Stop(_) => Self::Stop(Some(duration)),
+            Self::Expired => self,
+        }
+    }
+}
 impl<'a> FromParam<'a> for ClockEvent {
     type Error = ParseError;
     fn from_param(param: &'a str) -> Result<Self, Self::Error> {
@@ -27,12 +44,36 @@ impl<'a> FromParam<'a> for ClockEvent {
     }
 }
 
-#[derive(Debug, Clone, Copy, Serialize, EnumString)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, EnumString, AsRefStr)]
 #[strum(ascii_case_insensitive)]
 pub enum CounterEvent {
     Set(u64),
     Increment,
     Decrement,
+    Add(u64),
+    Subtract(u64),
+}
+impl CounterEvent {
+    /// Fills in `?value=`; `add` and `subtract` default to 1.
+    pub fn with_value(self, value: Option<u64>) -> Self {
+        match (self, value) {
+            (Self::Set(_), Some(value)) => Self::Set(value),
+            (Self::Add(_), value) => Self::Add(value.unwrap_or(1)),
+            (Self::Subtract(_), value) => Self::Subtract(value.unwrap_or(1)),
+            _ => self,
+        }
+    }
+    /// How much the event changes the counter by, unless it sets it outright.
+    /// Wide enough for any `u64` either way.
+    pub fn delta(&self) -> Option<i128> {
+        match *self {
+            Self::Set(_) => None,
+            Self::Increment => Some(1),
+            Self::Decrement => Some(-1),
+            Self::Add(n) => Some(n.into()),
+            Self::Subtract(n) => Some(-i128::from(n)),
+        }
+    }
 }
 impl<'a> FromParam<'a> for CounterEvent {
     type Error = ParseError;
@@ -41,13 +82,13 @@ impl<'a> FromParam<'a> for CounterEvent {
     }
 }
 
-#[derive(Debug, Clone, Copy, Serialize)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub enum ToggleState {
     Active,
     Inactive,
 }
 
-#[derive(Debug, Clone, Copy, Serialize, EnumString)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, EnumString, AsRefStr)]
 #[strum(ascii_case_insensitive)]
 pub enum ToggleEvent {
     Activate,
@@ -60,11 +101,43 @@ impl<'a> FromParam<'a> for ToggleEvent {
     }
 }
 
-#[derive(Debug, Clone, Serialize, EnumString)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PossessionState {
+    Home,
+    Away,
+    #[default]
+    None,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, EnumString, AsRefStr)]
+#[strum(ascii_case_insensitive)]
+pub enum PossessionEvent {
+    Home,
+    Away,
+    /// Hands the ball to the other team, e.g. for the alternating arrow.
+    Flip,
+    Clear,
+}
+impl<'a> FromParam<'a> for PossessionEvent {
+    type Error = ParseError;
+    fn from_param(param: &'a str) -> Result<Self, Self::Error> {
+        param.try_into()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, EnumString, AsRefStr)]
 #[strum(ascii_case_insensitive)]
 pub enum LabelEvent {
     Set(String),
 }
+impl LabelEvent {
+    pub fn with_value(self, value: Option<String>) -> Self {
+        match (self, value) {
+            (Self::Set(_), Some(value)) => Self::Set(value),
+            (event, _) => event,
+        }
+    }
+}
 impl<'a> FromParam<'a> for LabelEvent {
     type Error = ParseError;
     fn from_param(param: &'a str) -> Result<Self, Self::Error> {
@@ -0,0 +1,534 @@
+use std::collections::{BTreeMap, HashMap};
+
+use rocket::tokio::{
+    self, select,
+    sync::{
+        broadcast::{error::RecvError, Sender},
+        mpsc,
+    },
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tracing::error;
+
+use crate::{
+    component::{Component, GlobalComponent, TeamComponent},
+    event::{
+        states::{CounterEvent, ToggleEvent},
+        Event, LogEvent, Shareable,
+    },
+    metrics::METRICS,
+    replication::Replication,
+    scoreboard::{ComponentState, Scoreboard},
+};
+
+/// When team foul counts start again from zero.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FoulReset {
+    Period,
+    Half,
+    Game,
+}
+
+/// Reached once a team has committed `fouls` fouls, e.g. `one_and_one`
+/// at 7 and `double_bonus` at 10.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BonusLevel {
+    pub name: String,
+    pub fouls: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FoulProfile {
+    #[serde(default = "default_reset")]
+    pub reset: FoulReset,
+    #[serde(default = "default_periods_per_half")]
+    pub periods_per_half: u64,
+    /// Clears the counts when the period changes as `reset` demands.
+    #[serde(default = "default_auto_reset")]
+    pub auto_reset: bool,
+    pub levels: Vec<BonusLevel>,
+    /// Fouls that turn the team foul warning on. Defaults to the first level.
+    pub warning_at: Option<u64>,
+    /// Shows the warning only at `warning_at` and every this many fouls
+    /// after, e.g. at 9, 14 and 19 with 5.
+    pub warning_every: Option<u64>,
+}
+
+fn default_reset() -> FoulReset {
+    FoulReset::Period
+}
+fn default_periods_per_half() -> u64 {
+    2
+}
+fn default_auto_reset() -> bool {
+    true
+}
+
+impl FoulProfile {
+    fn new(reset: FoulReset, levels: &[(&str, u64)]) -> Self {
+        Self {
+            reset,
+            periods_per_half: default_periods_per_half(),
+            auto_reset: true,
+            levels: levels
+                .iter()
+                .map(|(name, fouls)| BonusLevel {
+                    name: (*name).into(),
+                    fouls: *fouls,
+                })
+                .collect(),
+            warning_at: None,
+            warning_every: None,
+        }
+    }
+    /// The highest level reached, if any.
+    pub fn level(&self, fouls: u64) -> Option<&str> {
+        let reached = self.levels.iter().filter(|level| fouls >= level.fouls);
+        reached
+            .max_by_key(|level| level.fouls)
+            .map(|level| level.name.as_str())
+    }
+    pub fn warning(&self, fouls: u64) -> bool {
+        let first_level = self.levels.iter().map(|level| level.fouls).min();
+        let Some(warning_at) = self.warning_at.or(first_level) else {
+            return false;
+        };
+        match self.warning_every {
+            Some(every) => fouls >= warning_at && (fouls - warning_at).is_multiple_of(every),
+            None => fouls >= warning_at,
+        }
+    }
+    fn resets_between(&self, from: u64, to: u64) -> bool {
+        if !self.auto_reset || to <= from {
+            return false;
+        }
+        let half = |period: u64| period.saturating_sub(1) / self.periods_per_half.max(1);
+        match self.reset {
+            FoulReset::Period => true,
+            FoulReset::Half => half(from) != half(to),
+            FoulReset::Game => false,
+        }
+    }
+}
+
+/// The `team_fouls` section of the Rocket config.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct TeamFoulsConfig {
+    /// Name of the profile in use at startup.
+    pub profile: String,
+    /// Merged over the built in `classic`, `fiba`, `nba`, `ncaa_men` and
+    /// `ncaa_women` profiles.
+    pub profiles: HashMap<String, FoulProfile>,
+}
+impl Default for TeamFoulsConfig {
+    fn default() -> Self {
+        Self {
+            profile: "classic".into(),
+            profiles: HashMap::new(),
+        }
+    }
+}
+impl TeamFoulsConfig {
+    fn profiles(&self) -> BTreeMap<String, FoulProfile> {
+        let mut profiles = BTreeMap::from([
+            (
+                // No bonus or resets; the warning shows one foul before
+                // every fifth from the tenth on.
+                "classic".into(),
+                FoulProfile {
+                    auto_reset: false,
+                    warning_at: Some(9),
+                    warning_every: Some(5),
+                    ..FoulProfile::new(FoulReset::Game, &[])
+                },
+            ),
+            (
+                "fiba".into(),
+                FoulProfile::new(FoulReset::Period, &[("penalty", 5)]),
+            ),
+            (
+                "nba".into(),
+                FoulProfile::new(FoulReset::Period, &[("penalty", 5)]),
+            ),
+            (
+                "ncaa_men".into(),
+                FoulProfile::new(FoulReset::Half, &[("one_and_one", 7), ("double_bonus", 10)]),
+            ),
+            (
+                "ncaa_women".into(),
+                FoulProfile::new(FoulReset::Period, &[("double_bonus", 5)]),
+            ),
+        ]);
+        profiles.extend(self.profiles.clone());
+        profiles
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TeamBonus {
+    pub fouls: u64,
+    /// The bonus level the team's fouls have given its opponent.
+    pub bonus: Option<String>,
+    pub warning: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FoulStatus {
+    pub profile: String,
+    pub home: TeamBonus,
+    pub away: TeamBonus,
+}
+
+fn counter_value(state: &Scoreboard, component: Component) -> u64 {
+    match state.find(component) {
+        Some(ComponentState::Counter(counter)) => counter.value,
+        _ => 0,
+    }
+}
+
+fn toggle_active(state: &Scoreboard, component: Component) -> bool {
+    matches!(state.find(component), Some(ComponentState::Toggle(toggle)) if toggle.is_active())
+}
+
+fn team(component: Component, team: TeamComponent) -> Component {
+    match component {
+        Component::Away(_) => Component::Away(team),
+        _ => Component::Home(team),
+    }
+}
+
+/// Team foul counts, bonus levels and the active profile.
+#[derive(Debug, Clone)]
+pub struct TeamFouls {
+    profiles: BTreeMap<String, FoulProfile>,
+    active: Shareable<String>,
+    state: Shareable<Scoreboard>,
+    refresh: mpsc::Sender<()>,
+}
+impl TeamFouls {
+    pub fn profiles(&self) -> &BTreeMap<String, FoulProfile> {
+        &self.profiles
+    }
+    fn profile(&self) -> (String, FoulProfile) {
+        let name = self.active.data.lock().unwrap().clone();
+        let profile = self.profiles[&name].clone();
+        (name, profile)
+    }
+    /// Returns false for unknown profiles.
+    pub fn set_profile(&self, name: &str) -> bool {
+        if !self.profiles.contains_key(name) {
+            return false;
+        }
+        *self.active.data.lock().unwrap() = name.into();
+        let _ = self.refresh.try_send(());
+        true
+    }
+    pub fn status(&self) -> FoulStatus {
+        let (name, profile) = self.profile();
+        let state = self.state.data.lock().unwrap();
+        let bonus = |component| {
+            let fouls = counter_value(&state, component);
+            TeamBonus {
+                fouls,
+                bonus: profile.level(fouls).map(String::from),
+                warning: profile.warning(fouls),
+            }
+        };
+        FoulStatus {
+            profile: name,
+            home: bonus(Component::Home(TeamComponent::TeamFouls)),
+            away: bonus(Component::Away(TeamComponent::TeamFouls)),
+        }
+    }
+    /// Events that bring the warning toggles in line with the foul counts.
+    fn warning_events(&self, trigger: Option<&LogEvent>) -> Vec<LogEvent> {
+        let (_, profile) = self.profile();
+        let state = self.state.data.lock().unwrap();
+        let mut events = vec![];
+        for fouls in [
+            Component::Home(TeamComponent::TeamFouls),
+            Component::Away(TeamComponent::TeamFouls),
+        ] {
+            let warning = team(fouls, TeamComponent::TeamFoulWarning);
+            let active = profile.warning(counter_value(&state, fouls));
+            if active == toggle_active(&state, warning) {
+                continue;
+            }
+            let event = Event::Toggle(match active {
+                true => ToggleEvent::Activate,
+                false => ToggleEvent::Deactivate,
+            });
+            events.push(match trigger {
+                Some(trigger) => trigger.derive(warning, event),
+                None => LogEvent::new_now(warning, event),
+            });
+        }
+        events
+    }
+}
+
+/// Follows the foul counts on a private copy of the scoreboard, clearing
+/// them on period changes and switching the warnings. Like the rules, this
+/// only acts on the primary.
+pub fn start_team_fouls(
+    config: TeamFoulsConfig,
+    state: Scoreboard,
+    replication: Replication,
+    event_sender: Sender<LogEvent>,
+    data_channel: Sender<Value>,
+) -> TeamFouls {
+    let profiles = config.profiles();
+    if !profiles.contains_key(&config.profile) {
+        panic!(
+            "invalid `team_fouls` config: unknown profile `{}`",
+            config.profile
+        );
+    }
+    let (refresh, mut refresh_recv) = mpsc::channel(1);
+    let fouls = TeamFouls {
+        profiles,
+        active: config.profile.into(),
+        state: state.into(),
+        refresh,
+    };
+
+    let data_fouls = fouls.clone();
+    let mut data_recv = data_channel.subscribe();
+    tokio::spawn(async move {
+        loop {
+            let Ok(Value::Null) = data_recv.recv().await else {
+                continue;
+            };
+            let _ = data_channel.send(json!({ "team_fouls": data_fouls.status() }));
+        }
+    });
+
+    let task_fouls = fouls.clone();
+    let mut recv = event_sender.subscribe();
+    tokio::spawn(async move {
+        let fouls = task_fouls;
+        let period = Component::Global(GlobalComponent::Period);
+        loop {
+            let trigger = select! {
+                event = recv.recv() => match event {
+                    Ok(event) => event,
+                    Err(RecvError::Lagged(skipped)) => {
+                        METRICS.record_lag("team_fouls", skipped);
+                        continue;
+                    }
+                    Err(RecvError::Closed) => break,
+                },
+                Some(()) = refresh_recv.recv() => {
+                    if !replication.is_secondary() {
+                        send_all(&event_sender, fouls.warning_events(None));
+                    }
+                    continue;
+                }
+            };
+            let before = {
+                let mut state = fouls.state.data.lock().unwrap();
+                let before = counter_value(&state, period);
+                state.apply_event(&trigger);
+                before
+            };
+            if replication.is_secondary() || matches!(trigger.event, Event::Restore(_)) {
+                continue;
+            }
+            if trigger.component == period {
+                let after = counter_value(&fouls.state.data.lock().unwrap(), period);
+                if fouls.profile().1.resets_between(before, after) {
+                    let clear = [
+                        Component::Home(TeamComponent::TeamFouls),
+                        Component::Away(TeamComponent::TeamFouls),
+                    ]
+                    .map(|component| {
+                        trigger.derive(component, Event::Counter(CounterEvent::Set(0)))
+                    });
+                    send_all(&event_sender, clear.into());
+                }
+            }
+            if matches!(
+                trigger.component,
+                Component::Home(TeamComponent::TeamFouls)
+                    | Component::Away(TeamComponent::TeamFouls)
+            ) {
+                send_all(&event_sender, fouls.warning_events(Some(&trigger)));
+            }
+        }
+    });
+    fouls
+}
+
+fn send_all(event_sender: &Sender<LogEvent>, events: Vec<LogEvent>) {
+    for event in events {
+        if let Err(e) = event_sender.send(event) {
+            error!(error = %e, "team fouls: failed to send event");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use rocket::tokio::{
+        sync::broadcast::{self, Receiver},
+        time::timeout,
+    };
+
+    use super::*;
+    use crate::{
+        component::{
+            counter::{CounterBounds, InternalCounter},
+            toggle::InteralToggle,
+        },
+        replication::ReplicationConfig,
+        scoreboard::ScoreboardComponent,
+    };
+
+    async fn next(events: &mut Receiver<LogEvent>) -> LogEvent {
+        timeout(Duration::from_secs(1), events.recv())
+            .await
+            .unwrap()
+            .unwrap()
+    }
+
+    fn profile(name: &str) -> FoulProfile {
+        TeamFoulsConfig::default().profiles()[name].clone()
+    }
+
+    #[test]
+    fn classic_matches_the_old_team_foul_counter() {
+        let classic = profile("classic");
+        // The toggle the old counter switched on every change.
+        let mut warning = false;
+        for fouls in 0..=30u64 {
+            if fouls > 5 && (fouls + 1) % 5 == 0 {
+                warning = true;
+            } else if fouls > 5 && (fouls + 2) % 5 == 0 || fouls % 5 == 0 {
+                warning = false;
+            }
+            assert_eq!(classic.warning(fouls), warning, "at {fouls} fouls");
+        }
+        assert!([9, 14, 19].iter().all(|fouls| classic.warning(*fouls)));
+        assert_eq!(classic.level(30), None);
+        assert!(!classic.resets_between(1, 2));
+    }
+
+    #[test]
+    fn bonus_levels_start_at_the_documented_counts() {
+        for name in ["fiba", "nba", "ncaa_women"] {
+            let profile = profile(name);
+            let bonus = match name {
+                "ncaa_women" => "double_bonus",
+                _ => "penalty",
+            };
+            assert_eq!(profile.level(4), None, "{name}");
+            assert_eq!(profile.level(5), Some(bonus), "{name}");
+            assert_eq!(profile.level(12), Some(bonus), "{name}");
+            assert!(!profile.warning(4) && profile.warning(5), "{name}");
+        }
+        let ncaa_men = profile("ncaa_men");
+        assert_eq!(ncaa_men.level(6), None);
+        assert_eq!(ncaa_men.level(7), Some("one_and_one"));
+        assert_eq!(ncaa_men.level(9), Some("one_and_one"));
+        assert_eq!(ncaa_men.level(10), Some("double_bonus"));
+        assert!(!ncaa_men.warning(6) && ncaa_men.warning(7));
+    }
+
+    #[test]
+    fn counts_reset_at_the_documented_points() {
+        for name in ["fiba", "nba", "ncaa_women"] {
+            let profile = profile(name);
+            assert!(profile.resets_between(1, 2), "{name}");
+            assert!(profile.resets_between(3, 4), "{name}");
+            assert!(!profile.resets_between(2, 2), "{name}");
+            assert!(!profile.resets_between(2, 1), "{name}");
+        }
+        let ncaa_men = profile("ncaa_men");
+        assert!(!ncaa_men.resets_between(1, 2));
+        assert!(ncaa_men.resets_between(2, 3));
+        assert!(!ncaa_men.resets_between(3, 4));
+        assert!(ncaa_men.resets_between(1, 3));
+    }
+
+    #[rocket::async_test]
+    async fn clears_counts_and_warns_as_the_game_goes_on() {
+        let mut state = Scoreboard::default();
+        let counter = |name: &str| {
+            ComponentState::Counter(InternalCounter::new(
+                name.into(),
+                1,
+                CounterBounds::default(),
+            ))
+        };
+        let period = Component::Global(GlobalComponent::Period);
+        let home_fouls = Component::Home(TeamComponent::TeamFouls);
+        let home_warning = Component::Home(TeamComponent::TeamFoulWarning);
+        for (component, initial) in [
+            (period, counter("period")),
+            (home_fouls, counter("home_tf")),
+            (
+                Component::Away(TeamComponent::TeamFouls),
+                counter("away_tf"),
+            ),
+            (
+                home_warning,
+                ComponentState::Toggle(InteralToggle::new("home_tfw".into())),
+            ),
+        ] {
+            state.add_component(ScoreboardComponent::new(component, initial));
+        }
+        let config = TeamFoulsConfig {
+            profile: "fiba".into(),
+            ..Default::default()
+        };
+        let (event_sender, mut events) = broadcast::channel(64);
+        start_team_fouls(
+            config,
+            state,
+            Replication::new(ReplicationConfig::default()),
+            event_sender.clone(),
+            broadcast::channel(16).0,
+        );
+
+        event_sender
+            .send(LogEvent::new_now(
+                home_fouls,
+                Event::Counter(CounterEvent::Set(5)),
+            ))
+            .unwrap();
+        next(&mut events).await;
+        let warning = next(&mut events).await;
+        assert_eq!(warning.component, home_warning);
+        assert!(matches!(
+            warning.event,
+            Event::Toggle(ToggleEvent::Activate)
+        ));
+
+        event_sender
+            .send(LogEvent::new_now(
+                period,
+                Event::Counter(CounterEvent::Increment),
+            ))
+            .unwrap();
+        next(&mut events).await;
+        for _ in 0..2 {
+            let clear = next(&mut events).await;
+            assert!(matches!(
+                clear.component,
+                Component::Home(_) | Component::Away(_)
+            ));
+            assert!(matches!(clear.event, Event::Counter(CounterEvent::Set(0))));
+        }
+        let warning = next(&mut events).await;
+        assert_eq!(warning.component, home_warning);
+        assert!(matches!(
+            warning.event,
+            Event::Toggle(ToggleEvent::Deactivate)
+        ));
+    }
+}
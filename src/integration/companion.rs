@@ -0,0 +1,93 @@
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use crate::{
+    component::Component,
+    event::{parse_command, states::ToggleEvent, Event},
+    scoreboard::{ComponentState, Scoreboard},
+};
+
+const WHITE: &str = "#FFFFFF";
+const BLACK: &str = "#000000";
+const RED: &str = "#CC0000";
+const GREEN: &str = "#00A000";
+
+/// Parses a control surface action. Besides the usual route style commands,
+/// `<toggle>/toggle` flips a toggle, so a single button can drive it.
+pub fn companion_command(
+    path: &str,
+    value: Option<&str>,
+    scoreboard: &Scoreboard,
+) -> Option<(Component, Event)> {
+    let path = path.trim_matches('/');
+    if let Some((component, "toggle")) = path.rsplit_once('/') {
+        let component = Component::from_path(component)?;
+        let Some(ComponentState::Toggle(toggle)) = scoreboard.find(component) else {
+            return None;
+        };
+        let event = match toggle.is_active() {
+            true => ToggleEvent::Deactivate,
+            false => ToggleEvent::Activate,
+        };
+        return Some((component, Event::Toggle(event)));
+    }
+    parse_command(path, value)
+}
+
+/// Button state hints for a single data key. Colours are `#RRGGBB`.
+#[derive(Debug, Clone, Serialize)]
+pub struct Feedback {
+    pub active: bool,
+    pub text: String,
+    pub color: String,
+    pub bgcolor: String,
+}
+
+/// Optional `?on=` and `?off=` background overrides.
+#[derive(Debug, Clone, Default)]
+pub struct FeedbackColors {
+    pub on: Option<String>,
+    pub off: Option<String>,
+}
+
+/// Toggles are active while on (red), clocks while running (green) and
+/// counters while non-zero. An expired clock is shown red.
+pub fn feedback(value: &Value, colors: &FeedbackColors) -> Feedback {
+    let mut expired = false;
+    let (active, text, highlight) = match value {
+        Value::Bool(active) => (*active, if *active { "ON" } else { "OFF" }.into(), RED),
+        Value::Object(clock) => {
+            let running = clock.get("state").and_then(Value::as_str) == Some("Running");
+            let time = clock.get("time_remaining").and_then(Value::as_str);
+            let time = time.unwrap_or_default().to_string();
+            expired = !running && time == "00:00";
+            (running, time, GREEN)
+        }
+        Value::Number(number) => (number.as_f64() != Some(0.0), number.to_string(), BLACK),
+        Value::String(text) => (false, text.clone(), BLACK),
+        _ => (false, String::new(), BLACK),
+    };
+    let bgcolor = match (active, expired) {
+        (true, _) => colors.on.clone().unwrap_or(highlight.into()),
+        (false, true) => RED.into(),
+        (false, false) => colors.off.clone().unwrap_or(BLACK.into()),
+    };
+    Feedback {
+        active,
+        text,
+        color: WHITE.into(),
+        bgcolor,
+    }
+}
+
+pub fn all_feedback(data: &Value) -> Map<String, Value> {
+    let Value::Object(data) = data else {
+        return Map::new();
+    };
+    data.iter()
+        .map(|(key, value)| {
+            let feedback = feedback(value, &FeedbackColors::default());
+            (key.clone(), serde_json::to_value(feedback).unwrap())
+        })
+        .collect()
+}
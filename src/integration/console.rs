@@ -0,0 +1,218 @@
+use std::{
+    collections::HashMap,
+    io::Read,
+    thread,
+    time::{Duration, Instant},
+};
+
+use rocket::tokio::{self, io::AsyncReadExt, net::TcpStream, sync::mpsc, time::sleep};
+use serde::Deserialize;
+use tracing::{error, warn};
+
+use crate::{
+    component::Component,
+    event::{
+        states::{CounterEvent, LabelEvent, PossessionEvent, ToggleEvent},
+        Event,
+    },
+};
+
+use super::{
+    decoder::{parse_clock, ClockTracker, InputProtocol},
+    IntegrationSender,
+};
+
+/// An entry of `console_inputs` in the Rocket config.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ConsoleInputConfig {
+    /// Serial device the console is attached to, e.g. `/dev/ttyUSB0`.
+    pub device: Option<String>,
+    #[serde(default = "default_baud_rate")]
+    pub baud_rate: u32,
+    /// `host:port` of a console that streams over TCP instead.
+    pub tcp: Option<String>,
+    #[serde(default)]
+    pub protocol: InputProtocol,
+    #[serde(default = "default_separator")]
+    pub separator: char,
+    /// Fields of each frame, in order.
+    pub fields: Vec<ConsoleField>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ConsoleField {
+    /// Route style component path, e.g. `global/gameclock` or `home/score`.
+    /// Fields without a component are skipped.
+    pub component: Option<String>,
+    /// Width for fixed width protocols.
+    #[serde(default)]
+    pub width: usize,
+}
+
+fn default_baud_rate() -> u32 {
+    9600
+}
+fn default_separator() -> char {
+    ','
+}
+
+/// Turns changes between console frames into scoreboard events.
+struct ConsoleMirror {
+    components: Vec<Option<Component>>,
+    clocks: HashMap<Component, ClockTracker>,
+    last: HashMap<Component, String>,
+}
+impl ConsoleMirror {
+    fn new(fields: &[ConsoleField]) -> Self {
+        let components = fields
+            .iter()
+            .map(|field| {
+                let component = Component::from_path(field.component.as_deref()?);
+                if component.is_none() {
+                    error!(component = ?field.component, "console: unknown component");
+                }
+                component
+            })
+            .collect();
+        Self {
+            components,
+            clocks: HashMap::new(),
+            last: HashMap::new(),
+        }
+    }
+    fn events(&mut self, frame: Vec<String>, now: Instant) -> Vec<(Component, Event)> {
+        let mut events = vec![];
+        for (component, value) in self.components.iter().zip(frame) {
+            let Some(component) = *component else {
+                continue;
+            };
+            if component.is_clock() {
+                let tracker = self.clocks.entry(component).or_default();
+                if let Some(event) = parse_clock(&value).and_then(|v| tracker.update(v, now)) {
+                    events.push((component, Event::Clock(event)));
+                }
+                continue;
+            }
+            if self.last.get(&component) == Some(&value) {
+                continue;
+            }
+            let event = if component.is_counter() {
+                value
+                    .parse()
+                    .ok()
+                    .map(|v| Event::Counter(CounterEvent::Set(v)))
+            } else if component.is_toggle() {
+                Some(Event::Toggle(match value.as_str() {
+                    "" | "0" => ToggleEvent::Deactivate,
+                    _ => ToggleEvent::Activate,
+                }))
+            } else if component.is_label() {
+                Some(Event::Label(LabelEvent::Set(value.clone())))
+            } else if component.is_possession() {
+                Some(Event::Possession(match value.parse() {
+                    Ok(PossessionEvent::Home) => PossessionEvent::Home,
+                    Ok(PossessionEvent::Away) => PossessionEvent::Away,
+                    _ => PossessionEvent::Clear,
+                }))
+            } else {
+                None
+            };
+            self.last.insert(component, value);
+            if let Some(event) = event {
+                events.push((component, event));
+            }
+        }
+        events
+    }
+}
+
+/// Reads the serial device on a blocking thread, reopening it on errors.
+fn read_serial(device: String, baud_rate: u32, bytes: mpsc::Sender<Vec<u8>>) {
+    thread::spawn(move || loop {
+        let port = serialport::new(&device, baud_rate)
+            .timeout(Duration::from_millis(500))
+            .open();
+        let mut port = match port {
+            Ok(port) => port,
+            Err(e) => {
+                warn!(%device, error = %e, "console: failed to open");
+                thread::sleep(Duration::from_secs(2));
+                continue;
+            }
+        };
+        let mut buffer = [0u8; 1024];
+        loop {
+            match port.read(&mut buffer) {
+                Ok(0) => {}
+                Ok(size) => {
+                    if bytes.blocking_send(buffer[..size].to_vec()).is_err() {
+                        return;
+                    }
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::TimedOut => {}
+                Err(e) => {
+                    warn!(%device, error = %e, "console: failed to read");
+                    break;
+                }
+            }
+        }
+    });
+}
+
+fn read_tcp(address: String, bytes: mpsc::Sender<Vec<u8>>) {
+    tokio::spawn(async move {
+        loop {
+            let mut stream = match TcpStream::connect(&address).await {
+                Ok(stream) => stream,
+                Err(e) => {
+                    warn!(%address, error = %e, "console: failed to connect");
+                    sleep(Duration::from_secs(2)).await;
+                    continue;
+                }
+            };
+            let mut buffer = [0u8; 1024];
+            loop {
+                match stream.read(&mut buffer).await {
+                    Ok(0) => break,
+                    Ok(size) => {
+                        if bytes.send(buffer[..size].to_vec()).await.is_err() {
+                            return;
+                        }
+                    }
+                    Err(e) => {
+                        warn!(%address, error = %e, "console: failed to read");
+                        break;
+                    }
+                }
+            }
+            sleep(Duration::from_secs(2)).await;
+        }
+    });
+}
+
+pub fn start_console_inputs(inputs: Vec<ConsoleInputConfig>, sender: IntegrationSender) {
+    for input in inputs {
+        let (send, mut recv) = mpsc::channel(64);
+        match (&input.device, &input.tcp) {
+            (Some(device), _) => read_serial(device.clone(), input.baud_rate, send),
+            (None, Some(address)) => read_tcp(address.clone(), send),
+            (None, None) => {
+                error!("console: input needs a device or tcp address");
+                continue;
+            }
+        }
+        let widths = input.fields.iter().map(|field| field.width).collect();
+        let mut decoder = input.protocol.decoder(widths, input.separator);
+        let mut mirror = ConsoleMirror::new(&input.fields);
+        let sender = sender.clone();
+        tokio::spawn(async move {
+            while let Some(bytes) = recv.recv().await {
+                for frame in decoder.feed(&bytes) {
+                    for (component, event) in mirror.events(frame, Instant::now()) {
+                        sender.send("console", component, event);
+                    }
+                }
+            }
+        });
+    }
+}
@@ -0,0 +1,224 @@
+use std::time::{Duration, Instant};
+
+use serde::Deserialize;
+
+use crate::event::states::ClockEvent;
+
+const STX: u8 = 0x02;
+const ETX: u8 = 0x03;
+
+/// Splits a console's byte stream into frames of raw field values.
+pub trait Decoder: Send {
+    fn feed(&mut self, bytes: &[u8]) -> Vec<Vec<String>>;
+}
+
+/// Selects the decoder with `protocol = "..."` in an input's config.
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InputProtocol {
+    /// `STX` fixed width fields `ETX`, as written by the `framed_text` encoder.
+    #[default]
+    FramedText,
+    /// Newline terminated lines of `separator` delimited fields.
+    Delimited,
+}
+impl InputProtocol {
+    pub fn decoder(&self, widths: Vec<usize>, separator: char) -> Box<dyn Decoder> {
+        match self {
+            InputProtocol::FramedText => Box::new(FramedText {
+                widths,
+                buffer: vec![],
+            }),
+            InputProtocol::Delimited => Box::new(Delimited {
+                separator,
+                buffer: vec![],
+            }),
+        }
+    }
+}
+
+pub struct FramedText {
+    widths: Vec<usize>,
+    buffer: Vec<u8>,
+}
+impl Decoder for FramedText {
+    fn feed(&mut self, bytes: &[u8]) -> Vec<Vec<String>> {
+        self.buffer.extend_from_slice(bytes);
+        let mut frames = vec![];
+        while let Some(end) = self.buffer.iter().position(|byte| *byte == ETX) {
+            let frame: Vec<u8> = self.buffer.drain(..=end).collect();
+            let Some(start) = frame.iter().rposition(|byte| *byte == STX) else {
+                continue;
+            };
+            let body = String::from_utf8_lossy(&frame[start + 1..frame.len() - 1]).to_string();
+            let mut fields = vec![];
+            let mut chars = body.chars();
+            for width in &self.widths {
+                let field: String = chars.by_ref().take(*width).collect();
+                fields.push(field.trim().to_string());
+            }
+            frames.push(fields);
+        }
+        frames
+    }
+}
+
+pub struct Delimited {
+    separator: char,
+    buffer: Vec<u8>,
+}
+impl Decoder for Delimited {
+    fn feed(&mut self, bytes: &[u8]) -> Vec<Vec<String>> {
+        self.buffer.extend_from_slice(bytes);
+        let mut frames = vec![];
+        while let Some(end) = self.buffer.iter().position(|byte| *byte == b'\n') {
+            let line: Vec<u8> = self.buffer.drain(..=end).collect();
+            let line = String::from_utf8_lossy(&line);
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            frames.push(
+                line.split(self.separator)
+                    .map(|field| field.trim().to_string())
+                    .collect(),
+            );
+        }
+        frames
+    }
+}
+
+/// Reads `MM:SS`, `H:MM:SS`, `SS` and `SS.t` clock displays.
+pub fn parse_clock(value: &str) -> Option<Duration> {
+    let mut seconds = 0.0;
+    for part in value.split(':') {
+        seconds = seconds * 60.0 + part.trim().parse::<f64>().ok()?;
+    }
+    Duration::try_from_secs_f64(seconds).ok()
+}
+
+/// Works out from consecutive clock readings whether the console's clock
+/// is running, since most consoles only send the time.
+#[derive(Debug, Default)]
+pub struct ClockTracker {
+    last: Option<Duration>,
+    running: bool,
+    last_change: Option<Instant>,
+}
+impl ClockTracker {
+    const MAX_TICK: Duration = Duration::from_secs(2);
+    const STALL: Duration = Duration::from_millis(1500);
+
+    pub fn update(&mut self, value: Duration, now: Instant) -> Option<ClockEvent> {
+        let Some(last) = self.last else {
+            self.last = Some(value);
+            self.last_change = Some(now);
+            return Some(ClockEvent::Set(value));
+        };
+        if value == last {
+            let stalled = self
+                .last_change
+                .is_some_and(|change| now.saturating_duration_since(change) > Self::STALL);
+            if self.running && stalled {
+                self.running = false;
+                return Some(ClockEvent::Stop(Some(value)));
+            }
+            return None;
+        }
+        self.last = Some(value);
+        self.last_change = Some(now);
+        if value < last && last - value <= Self::MAX_TICK {
+            if self.running {
+                return None;
+            }
+            self.running = true;
+            return Some(ClockEvent::Start(Some(value)));
+        }
+        if self.running {
+            self.running = false;
+            return Some(ClockEvent::Stop(Some(value)));
+        }
+        Some(ClockEvent::Set(value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::integration::{
+        encoder::{default_fields, Protocol},
+        flatten_data,
+    };
+
+    #[test]
+    fn decodes_what_the_encoder_writes() {
+        let data = flatten_data(&json!({
+            "game_clock": { "time_remaining": "09:58" },
+            "shot_clock": { "time_remaining": "24" },
+            "home_score": 105,
+            "away_score": 7,
+            "period": 4,
+            "siren": false,
+        }));
+        let fields = default_fields();
+        let packet = Protocol::FramedText.encoder(&fields).encode(&data);
+        let widths = fields.iter().map(|field| field.width).collect();
+        let mut decoder = InputProtocol::FramedText.decoder(widths, ',');
+
+        // Split mid-frame, after line noise, as a serial read might.
+        let (start, end) = packet.split_at(7);
+        assert!(decoder.feed(b"\x03junk").is_empty());
+        assert!(decoder.feed(start).is_empty());
+        let frames = decoder.feed(end);
+        assert_eq!(frames, [["09:58", "24", "105", "7", "4", "", "", "0"]]);
+    }
+
+    #[test]
+    fn decodes_delimited_lines() {
+        let mut decoder = InputProtocol::Delimited.decoder(vec![], ';');
+        assert!(decoder.feed(b"09:58; 3").is_empty());
+        assert_eq!(
+            decoder.feed(b";4\r\n\n12:00;0;0\n"),
+            [["09:58", "3", "4"], ["12:00", "0", "0"]]
+        );
+    }
+
+    #[test]
+    fn parses_clock_displays() {
+        assert_eq!(parse_clock("09:58"), Some(Duration::from_secs(598)));
+        assert_eq!(parse_clock("1:00:05"), Some(Duration::from_secs(3605)));
+        assert_eq!(parse_clock("24"), Some(Duration::from_secs(24)));
+        assert_eq!(parse_clock("4.5"), Some(Duration::from_millis(4500)));
+        for invalid in ["", "-1", "1e400", "inf", "NaN", "ab:cd"] {
+            assert_eq!(parse_clock(invalid), None, "{invalid}");
+        }
+    }
+
+    #[test]
+    fn tracks_the_clock_from_readings() {
+        let mut tracker = ClockTracker::default();
+        let now = Instant::now();
+        let at = |ms| now + Duration::from_millis(ms);
+        let secs = Duration::from_secs;
+        assert!(matches!(
+            tracker.update(secs(600), at(0)),
+            Some(ClockEvent::Set(_))
+        ));
+        assert!(matches!(
+            tracker.update(secs(599), at(1000)),
+            Some(ClockEvent::Start(Some(time))) if time == secs(599)
+        ));
+        assert!(tracker.update(secs(598), at(2000)).is_none());
+        assert!(tracker.update(secs(598), at(3000)).is_none());
+        assert!(matches!(
+            tracker.update(secs(598), at(3600)),
+            Some(ClockEvent::Stop(Some(time))) if time == secs(598)
+        ));
+        assert!(matches!(
+            tracker.update(secs(720), at(4000)),
+            Some(ClockEvent::Set(_))
+        ));
+    }
+}
@@ -0,0 +1,106 @@
+use serde::Deserialize;
+use serde_json::{Map, Value};
+
+const STX: u8 = 0x02;
+const ETX: u8 = 0x03;
+
+/// Turns flattened scoreboard data into the bytes a device expects.
+pub trait Encoder: Send {
+    fn encode(&self, data: &Map<String, Value>) -> Vec<u8>;
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Field {
+    /// Flattened data key, e.g. `home_score` or `game_clock/time_remaining`.
+    pub key: String,
+    pub width: usize,
+    #[serde(default)]
+    pub align: Align,
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Align {
+    Left,
+    #[default]
+    Right,
+}
+
+/// Selects the encoder with `protocol = "..."` in an output's config.
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Protocol {
+    /// `STX` fixed width fields `ETX`, XOR checksum as two hex digits, `CR`.
+    #[default]
+    FramedText,
+    /// One JSON object per line.
+    JsonLines,
+}
+impl Protocol {
+    pub fn encoder(&self, fields: &[Field]) -> Box<dyn Encoder> {
+        match self {
+            Protocol::FramedText => Box::new(FramedText {
+                fields: fields.to_vec(),
+            }),
+            Protocol::JsonLines => Box::new(JsonLines),
+        }
+    }
+}
+
+pub fn default_fields() -> Vec<Field> {
+    let field = |key: &str, width| Field {
+        key: key.into(),
+        width,
+        align: Align::Right,
+    };
+    vec![
+        field("game_clock/time_remaining", 5),
+        field("shot_clock/time_remaining", 5),
+        field("home_score", 3),
+        field("away_score", 3),
+        field("period", 1),
+        field("home_tf", 2),
+        field("away_tf", 2),
+        field("siren", 1),
+    ]
+}
+
+pub fn format_value(value: &Value) -> String {
+    match value {
+        Value::String(value) => value.clone(),
+        Value::Bool(value) => u8::from(*value).to_string(),
+        Value::Null => String::new(),
+        value => value.to_string(),
+    }
+}
+
+pub struct FramedText {
+    fields: Vec<Field>,
+}
+impl Encoder for FramedText {
+    fn encode(&self, data: &Map<String, Value>) -> Vec<u8> {
+        let mut packet = vec![STX];
+        for field in &self.fields {
+            let value = data.get(&field.key).map(format_value).unwrap_or_default();
+            let value: String = value.chars().take(field.width).collect();
+            let value = match field.align {
+                Align::Left => format!("{value:<width$}", width = field.width),
+                Align::Right => format!("{value:>width$}", width = field.width),
+            };
+            packet.extend(value.bytes());
+        }
+        packet.push(ETX);
+        let checksum = packet[1..].iter().fold(0u8, |sum, byte| sum ^ byte);
+        packet.extend(format!("{checksum:02X}\r").bytes());
+        packet
+    }
+}
+
+pub struct JsonLines;
+impl Encoder for JsonLines {
+    fn encode(&self, data: &Map<String, Value>) -> Vec<u8> {
+        let mut packet = Value::Object(data.clone()).to_string().into_bytes();
+        packet.push(b'\n');
+        packet
+    }
+}
@@ -0,0 +1,138 @@
+use std::{collections::HashMap, path::PathBuf, time::Duration};
+
+use rocket::tokio::{self, fs, sync::broadcast::Sender, time::interval};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use tracing::{error, warn};
+
+use crate::get_data;
+
+use super::encoder::format_value;
+
+/// Flat text fields for graphics software. Clocks are reduced to their
+/// displayed time, with the state in `<clock>_state`; other objects are
+/// flattened into `_` separated fields, e.g. `team_fouls_home_bonus`.
+pub fn feed_fields(data: &Value) -> Vec<(String, String)> {
+    fn push_fields(key: String, value: &Value, fields: &mut Vec<(String, String)>) {
+        match value {
+            Value::Object(clock) if clock.contains_key("time_remaining") => {
+                let field = |name: &str| clock.get(name).map(format_value).unwrap_or_default();
+                let state = (format!("{key}_state"), field("state"));
+                fields.push((key, field("time_remaining")));
+                fields.push(state);
+            }
+            Value::Object(object) => {
+                for (name, value) in object {
+                    let name = match key.as_str() {
+                        "" => name.clone(),
+                        key => format!("{key}_{name}"),
+                    };
+                    push_fields(name, value, fields);
+                }
+            }
+            value => fields.push((key, format_value(value))),
+        }
+    }
+    let mut fields = vec![];
+    if data.is_object() {
+        push_fields(String::new(), data, &mut fields);
+    }
+    fields
+}
+
+/// A single row array, the shape vMix JSON data sources read.
+pub fn to_vmix_json(fields: &[(String, String)]) -> String {
+    let row: Map<String, Value> = fields
+        .iter()
+        .map(|(key, value)| (key.clone(), Value::String(value.clone())))
+        .collect();
+    Value::Array(vec![Value::Object(row)]).to_string()
+}
+
+fn escape_xml(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+pub fn to_xml(fields: &[(String, String)]) -> String {
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<scoreboard>\n");
+    for (key, value) in fields {
+        xml.push_str(&format!("  <{key}>{}</{key}>\n", escape_xml(value)));
+    }
+    xml.push_str("</scoreboard>\n");
+    xml
+}
+
+fn escape_csv(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// A header row and a single value row.
+pub fn to_csv(fields: &[(String, String)]) -> String {
+    let header: Vec<_> = fields.iter().map(|(key, _)| escape_csv(key)).collect();
+    let values: Vec<_> = fields.iter().map(|(_, value)| escape_csv(value)).collect();
+    format!("{}\n{}\n", header.join(","), values.join(","))
+}
+
+/// The `text_files` section of the Rocket config.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct TextFilesConfig {
+    /// Directory that gets one `<field>.txt` per field. Disabled when unset.
+    pub directory: Option<PathBuf>,
+    pub refresh_ms: u64,
+}
+impl Default for TextFilesConfig {
+    fn default() -> Self {
+        Self {
+            directory: None,
+            refresh_ms: 200,
+        }
+    }
+}
+
+/// Keeps per field text files up to date for OBS text sources.
+pub fn start_text_files(config: TextFilesConfig, data_channels: Vec<Sender<Value>>) {
+    let Some(directory) = config.directory else {
+        return;
+    };
+    tokio::spawn(async move {
+        if let Err(e) = fs::create_dir_all(&directory).await {
+            error!(directory = %directory.display(), error = %e, "text files: failed to create directory");
+            return;
+        }
+        let mut written = HashMap::<String, String>::new();
+        let mut refresh = interval(Duration::from_millis(config.refresh_ms));
+        loop {
+            refresh.tick().await;
+            for (key, value) in feed_fields(&get_data(&data_channels).await) {
+                if written.get(&key) == Some(&value) {
+                    continue;
+                }
+                // Written aside and renamed so readers never see a partial file.
+                let path = directory.join(format!("{key}.txt"));
+                let partial = directory.join(format!(".{key}.txt.tmp"));
+                let result = match fs::write(&partial, &value).await {
+                    Ok(()) => fs::rename(&partial, &path).await,
+                    e => e,
+                };
+                match result {
+                    Ok(()) => {
+                        written.insert(key, value);
+                    }
+                    Err(e) => {
+                        warn!(path = %path.display(), error = %e, "text files: failed to write")
+                    }
+                }
+            }
+        }
+    });
+}
@@ -0,0 +1,408 @@
+use std::{
+    collections::HashMap,
+    fs::{self, File, OpenOptions},
+    io::{Read, Write},
+    path::PathBuf,
+    sync::mpsc as std_mpsc,
+    thread,
+    time::Duration,
+};
+
+use rocket::tokio::{
+    self, select,
+    sync::{
+        broadcast::{self, Sender},
+        mpsc,
+    },
+    time::timeout,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use tracing::{error, warn};
+
+use crate::event::{parse_command, Shareable};
+
+use super::{DataWatcher, IntegrationSender};
+
+/// The `midi` section of the Rocket config.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct MidiConfig {
+    /// Raw MIDI device, e.g. `/dev/snd/midiC1D0` or a `snd-virmidi` port.
+    /// Disabled when unset.
+    pub device: Option<String>,
+    /// Where the learned mapping is kept.
+    pub mapping_file: PathBuf,
+}
+impl Default for MidiConfig {
+    fn default() -> Self {
+        Self {
+            device: None,
+            mapping_file: "midi_mapping.json".into(),
+        }
+    }
+}
+
+/// A pad or knob, identified by channel (0-15) and note or controller number.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Control {
+    Note { channel: u8, note: u8 },
+    Cc { channel: u8, controller: u8 },
+}
+
+/// What a control does once bound.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MidiAction {
+    /// Route style command, e.g. `home/score/increment` or `global/siren/activate`.
+    pub command: String,
+    pub value: Option<String>,
+    /// Sends a CC's 0-127 value as the command value instead of treating
+    /// the CC as a button.
+    #[serde(default)]
+    pub pass_value: bool,
+    /// Flattened data key that lights the control's LED, e.g. `siren` or
+    /// `game_clock/state`.
+    pub feedback: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MidiBinding {
+    pub control: Control,
+    #[serde(flatten)]
+    pub action: MidiAction,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MidiMessage {
+    NoteOn {
+        channel: u8,
+        note: u8,
+        velocity: u8,
+    },
+    NoteOff {
+        channel: u8,
+        note: u8,
+    },
+    ControlChange {
+        channel: u8,
+        controller: u8,
+        value: u8,
+    },
+}
+
+/// Splits a raw MIDI byte stream into channel messages, following running
+/// status and skipping system exclusive and real time bytes.
+#[derive(Debug, Default)]
+struct MidiParser {
+    status: Option<u8>,
+    data: Vec<u8>,
+    sysex: bool,
+}
+impl MidiParser {
+    fn data_length(status: u8) -> usize {
+        match status & 0xF0 {
+            0xC0 | 0xD0 => 1,
+            0xF0 => match status {
+                0xF1 | 0xF3 => 1,
+                0xF2 => 2,
+                _ => 0,
+            },
+            _ => 2,
+        }
+    }
+    fn feed(&mut self, bytes: &[u8]) -> Vec<MidiMessage> {
+        let mut messages = vec![];
+        for &byte in bytes {
+            if byte >= 0xF8 {
+                continue;
+            }
+            if byte & 0x80 != 0 {
+                self.sysex = byte == 0xF0;
+                self.status = (byte != 0xF7 && !self.sysex).then_some(byte);
+                self.data.clear();
+                continue;
+            }
+            let Some(status) = self.status.filter(|_| !self.sysex) else {
+                continue;
+            };
+            self.data.push(byte);
+            if self.data.len() < Self::data_length(status) {
+                continue;
+            }
+            let channel = status & 0x0F;
+            let message = match (status & 0xF0, self.data.as_slice()) {
+                (0x90, &[note, velocity]) if velocity > 0 => Some(MidiMessage::NoteOn {
+                    channel,
+                    note,
+                    velocity,
+                }),
+                (0x80 | 0x90, &[note, _]) => Some(MidiMessage::NoteOff { channel, note }),
+                (0xB0, &[controller, value]) => Some(MidiMessage::ControlChange {
+                    channel,
+                    controller,
+                    value,
+                }),
+                _ => None,
+            };
+            messages.extend(message);
+            self.data.clear();
+            if status >= 0xF0 {
+                self.status = None;
+            }
+        }
+        messages
+    }
+}
+
+/// The LED message for a control, lit when `on`.
+fn led_message(control: Control, on: bool) -> Vec<u8> {
+    let level = if on { 127 } else { 0 };
+    match control {
+        Control::Note { channel, note } => vec![0x90 | channel, note, level],
+        Control::Cc {
+            channel,
+            controller,
+        } => vec![0xB0 | channel, controller, level],
+    }
+}
+
+fn is_lit(value: &Value) -> bool {
+    match value {
+        Value::Bool(value) => *value,
+        Value::String(value) => value == "Running",
+        Value::Number(value) => value.as_f64() != Some(0.0),
+        _ => false,
+    }
+}
+
+fn load_mapping(path: &PathBuf) -> Vec<MidiBinding> {
+    let Ok(mapping) = fs::read_to_string(path) else {
+        return vec![];
+    };
+    serde_json::from_str(&mapping).unwrap_or_else(|e| {
+        error!("midi: ignoring invalid mapping {}: {e}", path.display());
+        vec![]
+    })
+}
+
+/// The mapping and learn mode, shared with the `/midi` routes.
+#[derive(Debug, Clone)]
+pub struct Midi {
+    mapping: Shareable<Vec<MidiBinding>>,
+    learning: Shareable<Option<MidiAction>>,
+    learned: Sender<MidiBinding>,
+    mapping_file: PathBuf,
+}
+impl Midi {
+    fn new(mapping_file: PathBuf) -> Self {
+        Self {
+            mapping: load_mapping(&mapping_file).into(),
+            learning: None.into(),
+            learned: broadcast::channel(16).0,
+            mapping_file,
+        }
+    }
+    pub fn mapping(&self) -> Vec<MidiBinding> {
+        self.mapping.data.lock().unwrap().clone()
+    }
+    pub fn set_mapping(&self, mapping: Vec<MidiBinding>) -> std::io::Result<()> {
+        let json = serde_json::to_string_pretty(&mapping).expect("mapping serializes");
+        fs::write(&self.mapping_file, json)?;
+        *self.mapping.data.lock().unwrap() = mapping;
+        Ok(())
+    }
+    /// Binds `action` to the next control that is pressed.
+    pub async fn learn(&self, action: MidiAction, wait: Duration) -> Option<MidiBinding> {
+        let mut learned = self.learned.subscribe();
+        *self.learning.data.lock().unwrap() = Some(action);
+        let binding = timeout(wait, learned.recv())
+            .await
+            .ok()
+            .and_then(Result::ok);
+        if binding.is_none() {
+            self.learning.data.lock().unwrap().take();
+        }
+        binding
+    }
+    fn bind(&self, control: Control, action: MidiAction) {
+        let binding = MidiBinding { control, action };
+        let mut mapping = self.mapping();
+        mapping.retain(|existing| existing.control != control);
+        mapping.push(binding.clone());
+        if let Err(e) = self.set_mapping(mapping) {
+            error!("midi: failed to save {}: {e}", self.mapping_file.display());
+        }
+        let _ = self.learned.send(binding);
+    }
+    fn binding(&self, control: Control) -> Option<MidiAction> {
+        let mapping = self.mapping.data.lock().unwrap();
+        let binding = mapping.iter().find(|binding| binding.control == control)?;
+        Some(binding.action.clone())
+    }
+}
+
+/// Reads the device on a blocking thread, reopening it on errors.
+fn read_device(device: String, bytes: mpsc::Sender<Vec<u8>>) {
+    thread::spawn(move || loop {
+        let mut file = match File::open(&device) {
+            Ok(file) => file,
+            Err(e) => {
+                warn!("midi: failed to open {device}: {e}");
+                thread::sleep(Duration::from_secs(2));
+                continue;
+            }
+        };
+        let mut buffer = [0u8; 256];
+        loop {
+            match file.read(&mut buffer) {
+                Ok(0) => break,
+                Ok(size) => {
+                    if bytes.blocking_send(buffer[..size].to_vec()).is_err() {
+                        return;
+                    }
+                }
+                Err(e) => {
+                    warn!("midi: failed to read {device}: {e}");
+                    break;
+                }
+            }
+        }
+        thread::sleep(Duration::from_secs(2));
+    });
+}
+
+/// Owns the device's output side, reopening it after write errors.
+fn start_writer(device: String) -> std_mpsc::Sender<Vec<u8>> {
+    let (send, recv) = std_mpsc::channel::<Vec<u8>>();
+    thread::spawn(move || {
+        let mut file = None;
+        for message in recv {
+            if file.is_none() {
+                file = OpenOptions::new()
+                    .write(true)
+                    .open(&device)
+                    .inspect_err(|e| warn!("midi: failed to open {device}: {e}"))
+                    .ok();
+            }
+            let Some(open_file) = file.as_mut() else {
+                continue;
+            };
+            if let Err(e) = open_file.write_all(&message) {
+                warn!("midi: failed to write to {device}: {e}");
+                file = None;
+            }
+        }
+    });
+    send
+}
+
+fn handle_message(
+    midi: &Midi,
+    message: MidiMessage,
+    cc_levels: &mut HashMap<Control, u8>,
+    sender: &IntegrationSender,
+) {
+    let (control, value) = match message {
+        MidiMessage::NoteOn { channel, note, .. } => (Control::Note { channel, note }, None),
+        MidiMessage::NoteOff { .. } => return,
+        MidiMessage::ControlChange {
+            channel,
+            controller,
+            value,
+        } => (
+            Control::Cc {
+                channel,
+                controller,
+            },
+            Some(value),
+        ),
+    };
+    let binding = midi.binding(control);
+    let value = match (value, &binding) {
+        (Some(value), Some(action)) if action.pass_value => Some(value.to_string()),
+        (Some(value), _) => {
+            // CCs act as buttons on the rising edge through the midpoint.
+            let last = cc_levels.insert(control, value).unwrap_or(0);
+            if value < 64 || last >= 64 {
+                return;
+            }
+            None
+        }
+        (None, _) => None,
+    };
+    if let Some(action) = midi.learning.data.lock().unwrap().take() {
+        midi.bind(control, action);
+        return;
+    }
+    let Some(action) = binding else {
+        return;
+    };
+    let value = value.or(action.value);
+    let Some((component, event)) = parse_command(&action.command, value.as_deref()) else {
+        warn!("midi: no event for {}", action.command);
+        return;
+    };
+    sender.send("midi", component, event);
+}
+
+pub fn start_midi(
+    config: MidiConfig,
+    sender: IntegrationSender,
+    data_channels: Vec<Sender<Value>>,
+) -> Midi {
+    let midi = Midi::new(config.mapping_file);
+    let Some(device) = config.device else {
+        return midi;
+    };
+
+    let (send, mut recv) = mpsc::channel(64);
+    read_device(device.clone(), send);
+    let input = midi.clone();
+    let input_sender = sender.clone();
+    tokio::spawn(async move {
+        let mut parser = MidiParser::default();
+        let mut cc_levels = HashMap::new();
+        while let Some(bytes) = recv.recv().await {
+            for message in parser.feed(&bytes) {
+                handle_message(&input, message, &mut cc_levels, &input_sender);
+            }
+        }
+    });
+
+    let writer = start_writer(device);
+    let mut watcher = DataWatcher::new(sender.event_sender(), data_channels);
+    let mut learned = midi.learned.subscribe();
+    let feedback = midi.clone();
+    tokio::spawn(async move {
+        let mut data = Map::new();
+        loop {
+            let updated: Vec<MidiBinding> = select! {
+                changed = watcher.changed() => {
+                    let Some(changed) = changed else {
+                        break;
+                    };
+                    let updated = feedback
+                        .mapping()
+                        .into_iter()
+                        .filter(|binding| {
+                            let key = binding.action.feedback.as_ref();
+                            key.is_some_and(|key| changed.contains_key(key))
+                        })
+                        .collect();
+                    data.extend(changed);
+                    updated
+                }
+                Ok(binding) = learned.recv() => vec![binding],
+            };
+            for binding in updated {
+                let Some(key) = &binding.action.feedback else {
+                    continue;
+                };
+                let on = data.get(key).is_some_and(is_lit);
+                let _ = writer.send(led_message(binding.control, on));
+            }
+        }
+    });
+    midi
+}
@@ -0,0 +1,184 @@
+use rocket::tokio::sync::broadcast::{error::RecvError, Receiver, Sender};
+use serde_json::{Map, Value};
+use tracing::{error, warn};
+
+use crate::{
+    component::Component,
+    event::{Event, EventSource, LogEvent, Shareable},
+    get_data,
+    metrics::METRICS,
+    replication::Replication,
+    scoreboard::Scoreboard,
+};
+
+pub mod companion;
+pub mod console;
+pub mod decoder;
+pub mod encoder;
+pub mod feed;
+pub mod midi;
+pub mod mqtt;
+pub mod osc;
+pub mod serial;
+pub mod webhook;
+
+/// Sends events on behalf of integrations, held to the same checks as the
+/// HTTP API: nothing from a secondary, nothing the scoreboard's bounds reject.
+#[derive(Debug, Clone)]
+pub struct IntegrationSender {
+    event_sender: Sender<LogEvent>,
+    replication: Replication,
+    scoreboard: Shareable<Scoreboard>,
+}
+impl IntegrationSender {
+    pub fn new(
+        event_sender: Sender<LogEvent>,
+        replication: Replication,
+        scoreboard: Shareable<Scoreboard>,
+    ) -> Self {
+        Self {
+            event_sender,
+            replication,
+            scoreboard,
+        }
+    }
+    pub fn event_sender(&self) -> &Sender<LogEvent> {
+        &self.event_sender
+    }
+    /// Sends an event, logging why when it can't be.
+    pub fn send(&self, integration: &str, component: Component, event: Event) {
+        if self.replication.is_secondary() {
+            warn!(integration, ?component, "ignoring event on a secondary");
+            return;
+        }
+        let event = LogEvent {
+            source: EventSource::Integration(integration.into()),
+            ..LogEvent::new_now(component, event)
+        };
+        if let Err(e) = self.scoreboard.data.lock().unwrap().validate(&event) {
+            warn!(integration, ?component, error = %e, "rejected event");
+            return;
+        }
+        if let Err(e) = self.event_sender.send(event) {
+            error!(integration, error = %e, "failed to send event");
+        }
+    }
+}
+
+/// Flattens nested data into `/` separated keys, e.g. `game_clock/state`.
+pub fn flatten_data(data: &Value) -> Map<String, Value> {
+    fn flatten_into(prefix: &str, value: &Value, out: &mut Map<String, Value>) {
+        match value {
+            Value::Object(map) => {
+                for (key, value) in map {
+                    let key = match prefix {
+                        "" => key.clone(),
+                        prefix => format!("{prefix}/{key}"),
+                    };
+                    flatten_into(&key, value, out);
+                }
+            }
+            value => {
+                out.insert(prefix.into(), value.clone());
+            }
+        }
+    }
+    let mut out = Map::new();
+    flatten_into("", data, &mut out);
+    out
+}
+
+/// Reports the data fields that changed after each event, like the
+/// `data_stream` websocket does for displays.
+pub struct DataWatcher {
+    recv: Receiver<LogEvent>,
+    data_channels: Vec<Sender<Value>>,
+    last: Option<Map<String, Value>>,
+}
+impl DataWatcher {
+    pub fn new(event_sender: &Sender<LogEvent>, data_channels: Vec<Sender<Value>>) -> Self {
+        Self {
+            recv: event_sender.subscribe(),
+            data_channels,
+            last: None,
+        }
+    }
+    pub async fn data(&self) -> Value {
+        get_data(&self.data_channels).await
+    }
+    /// Waits for the next event that changes any field. The first call
+    /// reports every field.
+    pub async fn changed(&mut self) -> Option<Map<String, Value>> {
+        loop {
+            if self.last.is_some() {
+                match self.recv.recv().await {
+                    Ok(_) => {}
+                    Err(RecvError::Lagged(skipped)) => METRICS.record_lag("integration", skipped),
+                    Err(RecvError::Closed) => return None,
+                }
+            }
+            let data = flatten_data(&self.data().await);
+            let last = self.last.take().unwrap_or_default();
+            let changed: Map<String, Value> = data
+                .iter()
+                .filter(|(key, value)| last.get(*key) != Some(value))
+                .map(|(key, value)| (key.clone(), value.clone()))
+                .collect();
+            self.last = Some(data);
+            if !changed.is_empty() {
+                return Some(changed);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rocket::tokio::sync::broadcast;
+
+    use super::*;
+    use crate::{
+        component::{
+            counter::{CounterBounds, InternalCounter},
+            TeamComponent,
+        },
+        event::states::CounterEvent,
+        replication::ReplicationConfig,
+        scoreboard::{ComponentState, ScoreboardComponent},
+    };
+
+    #[test]
+    fn sends_only_what_the_api_would_accept() {
+        let score = Component::Home(TeamComponent::Score);
+        let mut scoreboard = Scoreboard::default();
+        let bounds = CounterBounds {
+            max: 3,
+            ..Default::default()
+        };
+        scoreboard.add_component(ScoreboardComponent::new(
+            score,
+            ComponentState::Counter(InternalCounter::new("score".into(), 3, bounds)),
+        ));
+        let replication = Replication::new(ReplicationConfig {
+            primary: Some("ws://127.0.0.1:1/replication/stream".into()),
+            ..Default::default()
+        });
+        let (event_sender, mut events) = broadcast::channel(16);
+        let sender = IntegrationSender::new(
+            event_sender,
+            replication.clone(),
+            Shareable::from(scoreboard),
+        );
+
+        sender.send("osc", score, Event::Counter(CounterEvent::Decrement));
+        assert!(events.try_recv().is_err(), "sent from a secondary");
+
+        replication.promote();
+        sender.send("osc", score, Event::Counter(CounterEvent::Increment));
+        assert!(events.try_recv().is_err(), "sent past the counter's max");
+
+        sender.send("osc", score, Event::Counter(CounterEvent::Decrement));
+        let event = events.try_recv().unwrap();
+        assert_eq!(event.source, EventSource::Integration("osc".into()));
+    }
+}
@@ -0,0 +1,260 @@
+use std::time::Duration;
+
+use rocket::tokio::{self, sync::broadcast::Sender, time::sleep};
+use rumqttc::{AsyncClient, Event as MqttEvent, MqttOptions, Packet, QoS};
+use serde::Deserialize;
+use serde_json::Value;
+use tracing::warn;
+
+use crate::event::parse_command;
+
+use super::{DataWatcher, IntegrationSender};
+
+/// The `mqtt` section of the Rocket config.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct MqttConfig {
+    /// Broker host name. The bridge is disabled when unset.
+    pub host: Option<String>,
+    pub port: u16,
+    pub client_id: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub topic_prefix: String,
+    /// Name of this board in topics, e.g. `scoreboard/<board>/home_score`.
+    pub board: String,
+    /// 0, 1 or 2.
+    pub qos: u8,
+    pub keep_alive_secs: u64,
+    pub reconnect_delay_ms: u64,
+}
+impl Default for MqttConfig {
+    fn default() -> Self {
+        Self {
+            host: None,
+            port: 1883,
+            client_id: "scoreboard".into(),
+            username: None,
+            password: None,
+            topic_prefix: "scoreboard".into(),
+            board: "main".into(),
+            qos: 1,
+            keep_alive_secs: 5,
+            reconnect_delay_ms: 2000,
+        }
+    }
+}
+impl MqttConfig {
+    fn qos(&self) -> QoS {
+        match self.qos {
+            0 => QoS::AtMostOnce,
+            1 => QoS::AtLeastOnce,
+            _ => QoS::ExactlyOnce,
+        }
+    }
+    fn state_topic(&self, field: &str) -> String {
+        format!("{}/{}/{field}", self.topic_prefix, self.board)
+    }
+    /// Commands are published to e.g. `scoreboard/main/command/home/score/set`
+    /// with the value as payload.
+    fn command_topic(&self) -> String {
+        format!("{}/{}/command/", self.topic_prefix, self.board)
+    }
+}
+
+fn payload(value: &Value) -> String {
+    match value {
+        Value::String(value) => value.clone(),
+        value => value.to_string(),
+    }
+}
+
+pub fn start_mqtt(
+    config: MqttConfig,
+    sender: IntegrationSender,
+    data_channels: Vec<Sender<Value>>,
+) {
+    let Some(host) = config.host.clone() else {
+        return;
+    };
+    let mut options = MqttOptions::new(&config.client_id, host, config.port);
+    options.set_keep_alive(Duration::from_secs(config.keep_alive_secs));
+    if let (Some(username), Some(password)) = (&config.username, &config.password) {
+        options.set_credentials(username, password);
+    }
+    let (client, mut event_loop) = AsyncClient::new(options, 64);
+
+    let mut watcher = DataWatcher::new(sender.event_sender(), data_channels);
+    let publish_client = client.clone();
+    let publish_config = config.clone();
+    tokio::spawn(async move {
+        while let Some(changed) = watcher.changed().await {
+            for (field, value) in changed {
+                let topic = publish_config.state_topic(&field);
+                let result = publish_client
+                    .publish(topic, publish_config.qos(), true, payload(&value))
+                    .await;
+                if let Err(e) = result {
+                    warn!(%field, error = %e, "mqtt: failed to publish");
+                }
+            }
+        }
+    });
+
+    tokio::spawn(async move {
+        let command_topic = config.command_topic();
+        loop {
+            let notification = match event_loop.poll().await {
+                Ok(notification) => notification,
+                Err(e) => {
+                    warn!(error = %e, "mqtt: connection error");
+                    sleep(Duration::from_millis(config.reconnect_delay_ms)).await;
+                    continue;
+                }
+            };
+            match notification {
+                MqttEvent::Incoming(Packet::ConnAck(_)) => {
+                    let topic = format!("{command_topic}#");
+                    if let Err(e) = client.subscribe(topic, config.qos()).await {
+                        warn!(error = %e, "mqtt: failed to subscribe");
+                    }
+                }
+                MqttEvent::Incoming(Packet::Publish(publish)) => {
+                    let Some(command) = publish.topic.strip_prefix(&command_topic) else {
+                        continue;
+                    };
+                    let value = String::from_utf8_lossy(&publish.payload);
+                    let value = Some(value.trim()).filter(|value| !value.is_empty());
+                    let Some((component, event)) = parse_command(command, value) else {
+                        warn!(topic = %publish.topic, "mqtt: no event for topic");
+                        continue;
+                    };
+                    sender.send("mqtt", component, event);
+                }
+                _ => {}
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use rocket::tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::{TcpListener, TcpStream},
+        sync::broadcast,
+        time::timeout,
+    };
+    use serde_json::json;
+
+    use super::*;
+    use crate::{
+        component::{Component, TeamComponent},
+        event::{states::CounterEvent, Event, EventSource, Shareable},
+        replication::{Replication, ReplicationConfig},
+        scoreboard::Scoreboard,
+    };
+
+    /// Reads one MQTT packet, returning its type and body.
+    async fn read_packet(stream: &mut TcpStream) -> (u8, Vec<u8>) {
+        let kind = stream.read_u8().await.unwrap() >> 4;
+        let (mut length, mut shift) = (0, 0);
+        loop {
+            let byte = stream.read_u8().await.unwrap();
+            length |= usize::from(byte & 0x7f) << shift;
+            shift += 7;
+            if byte & 0x80 == 0 {
+                break;
+            }
+        }
+        let mut body = vec![0; length];
+        stream.read_exact(&mut body).await.unwrap();
+        (kind, body)
+    }
+
+    fn publish_packet(topic: &str, payload: &str) -> Vec<u8> {
+        let length = 2 + topic.len() + payload.len();
+        let mut packet = vec![0x30, u8::try_from(length).unwrap()];
+        packet.extend((topic.len() as u16).to_be_bytes());
+        packet.extend(topic.as_bytes());
+        packet.extend(payload.as_bytes());
+        packet
+    }
+
+    /// Stands in for a broker: accepts the bridge, checks what it publishes
+    /// and subscribes to, then publishes `commands` to it.
+    #[rocket::async_test]
+    async fn publishes_state_and_receives_commands() {
+        let broker = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let config = MqttConfig {
+            host: Some("127.0.0.1".into()),
+            port: broker.local_addr().unwrap().port(),
+            qos: 0,
+            ..Default::default()
+        };
+        let (event_sender, _) = broadcast::channel(16);
+        let mut events = event_sender.subscribe();
+        let (data_channel, mut data_recv) = broadcast::channel::<Value>(16);
+        let data_responder = data_channel.clone();
+        tokio::spawn(async move {
+            while let Ok(value) = data_recv.recv().await {
+                if value.is_null() {
+                    let _ = data_responder.send(json!({ "home_score": 7 }));
+                }
+            }
+        });
+        let sender = IntegrationSender::new(
+            event_sender,
+            Replication::new(ReplicationConfig::default()),
+            Shareable::from(Scoreboard::default()),
+        );
+        start_mqtt(config, sender, vec![data_channel]);
+
+        let (mut stream, _) = timeout(Duration::from_secs(5), broker.accept())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(read_packet(&mut stream).await.0, 1, "connect");
+        stream.write_all(&[0x20, 0x02, 0x00, 0x00]).await.unwrap();
+
+        let mut subscribed = false;
+        let mut published = false;
+        while !(subscribed && published) {
+            let (kind, body) = read_packet(&mut stream).await;
+            match kind {
+                3 => {
+                    let topic_length = usize::from(u16::from_be_bytes([body[0], body[1]]));
+                    assert_eq!(&body[2..2 + topic_length], b"scoreboard/main/home_score");
+                    assert_eq!(&body[2 + topic_length..], b"7");
+                    published = true;
+                }
+                8 => {
+                    assert!(body.ends_with(b"scoreboard/main/command/#\x00"));
+                    let suback = [0x90, 0x03, body[0], body[1], 0x00];
+                    stream.write_all(&suback).await.unwrap();
+                    subscribed = true;
+                }
+                _ => {}
+            }
+        }
+
+        for (topic, payload) in [
+            ("scoreboard/main/command/home/nothing/set", "1"),
+            ("scoreboard/main/command/home/score/set", " 3 "),
+        ] {
+            stream
+                .write_all(&publish_packet(topic, payload))
+                .await
+                .unwrap();
+        }
+        let event = timeout(Duration::from_secs(5), events.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(event.component, Component::Home(TeamComponent::Score));
+        assert!(matches!(event.event, Event::Counter(CounterEvent::Set(3))));
+        assert!(matches!(event.source, EventSource::Integration(ref name) if name == "mqtt"));
+    }
+}
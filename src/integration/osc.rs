@@ -0,0 +1,155 @@
+use std::collections::HashMap;
+
+use rocket::tokio::{self, net::UdpSocket, sync::broadcast::Sender};
+use rosc::{OscMessage, OscPacket, OscType};
+use serde::Deserialize;
+use serde_json::Value;
+use tracing::{error, warn};
+
+use crate::event::parse_command;
+
+use super::{DataWatcher, IntegrationSender};
+
+/// The `osc` section of the Rocket config.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct OscConfig {
+    /// UDP address to receive OSC on, e.g. `0.0.0.0:9000`.
+    pub listen: Option<String>,
+    /// Addresses state changes are sent to.
+    pub targets: Vec<String>,
+    /// Prepended to output addresses and stripped from unmapped input.
+    pub prefix: String,
+    /// OSC address to command, e.g. `/horn` = `global/siren/activate`.
+    pub input: HashMap<String, String>,
+    /// Data field to OSC address, e.g. `home_score` = `/lights/home`.
+    /// Every field is sent under `prefix` when empty.
+    pub output: HashMap<String, String>,
+}
+impl Default for OscConfig {
+    fn default() -> Self {
+        Self {
+            listen: None,
+            targets: vec![],
+            prefix: "/scoreboard".into(),
+            input: HashMap::new(),
+            output: HashMap::new(),
+        }
+    }
+}
+impl OscConfig {
+    fn command<'a>(&'a self, address: &'a str) -> Option<&'a str> {
+        if let Some(command) = self.input.get(address) {
+            return Some(command);
+        }
+        address.strip_prefix(&self.prefix)
+    }
+    fn address(&self, field: &str) -> Option<String> {
+        if self.output.is_empty() {
+            return Some(format!("{}/{field}", self.prefix));
+        }
+        self.output.get(field).cloned()
+    }
+}
+
+fn osc_arg_to_value(arg: &OscType) -> Option<String> {
+    match arg {
+        OscType::Int(value) => Some(value.to_string()),
+        OscType::Long(value) => Some(value.to_string()),
+        OscType::Float(value) => Some(value.to_string()),
+        OscType::Double(value) => Some(value.to_string()),
+        OscType::String(value) => Some(value.clone()),
+        _ => None,
+    }
+}
+
+fn value_to_osc_arg(value: &Value) -> OscType {
+    match value {
+        Value::Bool(value) => OscType::Bool(*value),
+        Value::Number(number) => match number.as_i64() {
+            Some(value) => i32::try_from(value).map_or(OscType::Long(value), OscType::Int),
+            None => OscType::Float(number.as_f64().unwrap_or_default() as f32),
+        },
+        Value::String(value) => OscType::String(value.clone()),
+        value => OscType::String(value.to_string()),
+    }
+}
+
+fn handle_packet(config: &OscConfig, packet: OscPacket, sender: &IntegrationSender) {
+    let message = match packet {
+        OscPacket::Message(message) => message,
+        OscPacket::Bundle(bundle) => {
+            for packet in bundle.content {
+                handle_packet(config, packet, sender);
+            }
+            return;
+        }
+    };
+    let value = message.args.first().and_then(osc_arg_to_value);
+    let Some((component, event)) = config
+        .command(&message.addr)
+        .and_then(|command| parse_command(command, value.as_deref()))
+    else {
+        warn!(address = %message.addr, "osc: no event for address");
+        return;
+    };
+    sender.send("osc", component, event);
+}
+
+pub fn start_osc(config: OscConfig, sender: IntegrationSender, data_channels: Vec<Sender<Value>>) {
+    if let Some(listen) = config.listen.clone() {
+        let config = config.clone();
+        let sender = sender.clone();
+        tokio::spawn(async move {
+            let socket = match UdpSocket::bind(&listen).await {
+                Ok(socket) => socket,
+                Err(e) => {
+                    error!(%listen, error = %e, "osc: failed to listen");
+                    return;
+                }
+            };
+            let mut buffer = [0u8; rosc::decoder::MTU];
+            loop {
+                let Ok(size) = socket.recv(&mut buffer).await else {
+                    continue;
+                };
+                match rosc::decoder::decode_udp(&buffer[..size]) {
+                    Ok((_, packet)) => handle_packet(&config, packet, &sender),
+                    Err(e) => warn!(error = %e, "osc: failed to receive"),
+                }
+            }
+        });
+    }
+    if config.targets.is_empty() {
+        return;
+    }
+    let mut watcher = DataWatcher::new(sender.event_sender(), data_channels);
+    tokio::spawn(async move {
+        let socket = match UdpSocket::bind("0.0.0.0:0").await {
+            Ok(socket) => socket,
+            Err(e) => {
+                error!(error = %e, "osc: failed to open output socket");
+                return;
+            }
+        };
+        while let Some(changed) = watcher.changed().await {
+            for (field, value) in changed {
+                let Some(addr) = config.address(&field) else {
+                    continue;
+                };
+                let packet = OscPacket::Message(OscMessage {
+                    addr,
+                    args: vec![value_to_osc_arg(&value)],
+                });
+                let Ok(buffer) = rosc::encoder::encode(&packet) else {
+                    continue;
+                };
+                for target in &config.targets {
+                    if let Err(e) = socket.send_to(&buffer, target).await {
+                        warn!(%target, error = %e, "osc: failed to send");
+                    }
+                }
+            }
+        }
+    });
+}
@@ -0,0 +1,171 @@
+use std::{
+    io::Write,
+    sync::mpsc,
+    thread,
+    time::{Duration, Instant},
+};
+
+use rocket::tokio::{self, sync::broadcast::Sender, time::interval};
+use serde::Deserialize;
+use serde_json::Value;
+use tracing::warn;
+
+use crate::get_data;
+
+use super::{
+    encoder::{default_fields, Field, Protocol},
+    flatten_data,
+};
+
+/// An entry of `serial_outputs` in the Rocket config.
+#[derive(Debug, Clone, Deserialize)]
+pub struct SerialOutputConfig {
+    /// e.g. `/dev/ttyUSB0`.
+    pub device: String,
+    #[serde(default = "default_baud_rate")]
+    pub baud_rate: u32,
+    /// How often the data is encoded, so running clocks keep ticking.
+    #[serde(default = "default_refresh_ms")]
+    pub refresh_ms: u64,
+    /// Unchanged packets are still repeated this often.
+    #[serde(default = "default_repeat_ms")]
+    pub repeat_ms: u64,
+    #[serde(default)]
+    pub protocol: Protocol,
+    /// Fields sent by field based protocols, in order.
+    #[serde(default = "default_fields")]
+    pub fields: Vec<Field>,
+}
+
+fn default_baud_rate() -> u32 {
+    9600
+}
+fn default_refresh_ms() -> u64 {
+    100
+}
+fn default_repeat_ms() -> u64 {
+    1000
+}
+
+/// Owns the device on a blocking thread, reopening it after write errors.
+fn start_writer(device: String, baud_rate: u32) -> mpsc::Sender<Vec<u8>> {
+    let (send, recv) = mpsc::channel::<Vec<u8>>();
+    thread::spawn(move || {
+        let mut port = None;
+        for packet in recv {
+            if port.is_none() {
+                port = serialport::new(&device, baud_rate)
+                    .timeout(Duration::from_millis(500))
+                    .open()
+                    .inspect_err(|e| warn!(%device, error = %e, "serial: failed to open"))
+                    .ok();
+            }
+            let Some(open_port) = port.as_mut() else {
+                continue;
+            };
+            if let Err(e) = open_port.write_all(&packet) {
+                warn!(%device, error = %e, "serial: failed to write");
+                port = None;
+            }
+        }
+    });
+    send
+}
+
+pub fn start_serial_outputs(outputs: Vec<SerialOutputConfig>, data_channels: Vec<Sender<Value>>) {
+    for output in outputs {
+        let data_channels = data_channels.clone();
+        let writer = start_writer(output.device.clone(), output.baud_rate);
+        let encoder = output.protocol.encoder(&output.fields);
+        tokio::spawn(async move {
+            let mut refresh = interval(Duration::from_millis(output.refresh_ms));
+            let repeat = Duration::from_millis(output.repeat_ms);
+            let mut last: Option<(Vec<u8>, Instant)> = None;
+            loop {
+                refresh.tick().await;
+                let data = flatten_data(&get_data(&data_channels).await);
+                let packet = encoder.encode(&data);
+                if let Some((last_packet, sent)) = &last {
+                    if *last_packet == packet && sent.elapsed() < repeat {
+                        continue;
+                    }
+                }
+                if writer.send(packet.clone()).is_err() {
+                    break;
+                }
+                last = Some((packet, Instant::now()));
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use rocket::tokio::sync::broadcast;
+    use serde_json::json;
+    use serialport::{SerialPort, TTYPort};
+
+    use super::*;
+    use crate::integration::decoder::InputProtocol;
+
+    /// Writes to one end of a pty and reads the packets back from the other,
+    /// as a scoreboard on the end of the cable would.
+    #[rocket::async_test]
+    async fn writes_framed_packets_to_the_device() {
+        // The output opens the device itself, so only the pty's name is kept.
+        let (mut master, slave) = TTYPort::pair().unwrap();
+        let device = slave.name().unwrap();
+        drop(slave);
+        let (data_channel, mut data_recv) = broadcast::channel::<Value>(16);
+        let data_responder = data_channel.clone();
+        tokio::spawn(async move {
+            while let Ok(value) = data_recv.recv().await {
+                if value.is_null() {
+                    let _ = data_responder.send(json!({
+                        "game_clock": { "time_remaining": "09:58" },
+                        "home_score": 12,
+                        "away_score": 3,
+                        "period": 2,
+                        "siren": true,
+                    }));
+                }
+            }
+        });
+        let fields = default_fields();
+        let widths = fields.iter().map(|field| field.width).collect();
+        start_serial_outputs(
+            vec![SerialOutputConfig {
+                device,
+                baud_rate: default_baud_rate(),
+                refresh_ms: 50,
+                repeat_ms: default_repeat_ms(),
+                protocol: Protocol::FramedText,
+                fields,
+            }],
+            vec![data_channel],
+        );
+
+        let frame = tokio::task::spawn_blocking(move || {
+            let deadline = Instant::now() + Duration::from_secs(5);
+            let mut decoder = InputProtocol::FramedText.decoder(widths, ',');
+            let mut buffer = [0; 64];
+            loop {
+                // Reads fail until the output has the pty open.
+                match master.read(&mut buffer) {
+                    Ok(read) => {
+                        if let Some(frame) = decoder.feed(&buffer[..read]).pop() {
+                            return frame;
+                        }
+                    }
+                    Err(e) if Instant::now() > deadline => panic!("no packet: {e}"),
+                    Err(_) => thread::sleep(Duration::from_millis(50)),
+                }
+            }
+        })
+        .await
+        .unwrap();
+        assert_eq!(frame, ["09:58", "", "12", "3", "2", "", "", "1"]);
+    }
+}
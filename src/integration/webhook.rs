@@ -0,0 +1,414 @@
+use std::time::{Duration, SystemTime};
+
+use hmac::{Hmac, Mac};
+use rocket::tokio::{
+    self,
+    sync::broadcast::{error::RecvError, Sender},
+    time::sleep,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha2::Sha256;
+use tracing::{error, info_span, warn, Instrument};
+use uuid::Uuid;
+
+use crate::{
+    event::{EventPattern, LogEvent, Shareable},
+    get_data,
+    metrics::METRICS,
+    replication::Replication,
+};
+
+const DELIVERY_LOG_SIZE: usize = 1000;
+
+/// An entry of `webhooks` in the Rocket config.
+#[derive(Debug, Clone, Deserialize)]
+pub struct WebhookConfig {
+    pub url: String,
+    /// Signs the body as `X-Scoreboard-Signature: sha256=<hex hmac>`.
+    pub secret: Option<String>,
+    pub triggers: Vec<Trigger>,
+    #[serde(default = "default_max_attempts")]
+    pub max_attempts: u32,
+    /// Delay before the first retry, doubled for each further one.
+    #[serde(default = "default_backoff_ms")]
+    pub backoff_ms: u64,
+    #[serde(default = "default_timeout_ms")]
+    pub timeout_ms: u64,
+}
+
+fn default_max_attempts() -> u32 {
+    5
+}
+fn default_backoff_ms() -> u64 {
+    500
+}
+fn default_timeout_ms() -> u64 {
+    5000
+}
+
+/// e.g. `{ name = "goal", component = "home/score", kind = "increment" }`
+/// or `{ name = "match_end", component = "global/gameclock", kind = "expired", period = 4 }`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Trigger {
+    pub name: String,
+    #[serde(flatten)]
+    pub pattern: EventPattern,
+    /// Only fire in this period.
+    pub period: Option<u64>,
+}
+impl Trigger {
+    fn matches(&self, event: &LogEvent, data: &Value) -> bool {
+        self.pattern.matches(event)
+            && self
+                .period
+                .is_none_or(|period| data.get("period").and_then(Value::as_u64) == Some(period))
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub enum DeliveryStatus {
+    Pending,
+    Delivered,
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Delivery {
+    pub id: Uuid,
+    pub url: String,
+    pub trigger: String,
+    pub log_id: Uuid,
+    #[serde(with = "serde_millis")]
+    pub created: SystemTime,
+    pub attempts: u32,
+    pub status: DeliveryStatus,
+    pub response_status: Option<u16>,
+    pub error: Option<String>,
+}
+
+pub type DeliveryLog = Shareable<Vec<Delivery>>;
+
+fn update_delivery(log: &DeliveryLog, id: Uuid, update: impl FnOnce(&mut Delivery)) {
+    let mut log = log.data.lock().unwrap();
+    if let Some(delivery) = log.iter_mut().rev().find(|delivery| delivery.id == id) {
+        update(delivery);
+    }
+}
+
+fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("any key length");
+    mac.update(body);
+    let signature: String = mac
+        .finalize()
+        .into_bytes()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect();
+    format!("sha256={signature}")
+}
+
+async fn deliver(
+    client: reqwest::Client,
+    webhook: WebhookConfig,
+    body: Vec<u8>,
+    id: Uuid,
+    log: DeliveryLog,
+) {
+    let signature = webhook.secret.as_deref().map(|secret| sign(secret, &body));
+    let mut backoff = Duration::from_millis(webhook.backoff_ms);
+    for attempt in 1..=webhook.max_attempts {
+        let mut request = client
+            .post(&webhook.url)
+            .timeout(Duration::from_millis(webhook.timeout_ms))
+            .header("Content-Type", "application/json")
+            .header("X-Scoreboard-Delivery", id.to_string())
+            .body(body.clone());
+        if let Some(signature) = &signature {
+            request = request.header("X-Scoreboard-Signature", signature);
+        }
+        let (response_status, error) = match request.send().await {
+            Ok(response) if response.status().is_success() => {
+                update_delivery(&log, id, |delivery| {
+                    delivery.attempts = attempt;
+                    delivery.status = DeliveryStatus::Delivered;
+                    delivery.response_status = Some(response.status().as_u16());
+                    delivery.error = None;
+                });
+                return;
+            }
+            Ok(response) => (
+                Some(response.status().as_u16()),
+                format!("unexpected status {}", response.status()),
+            ),
+            Err(e) => (None, e.to_string()),
+        };
+        let last_attempt = attempt == webhook.max_attempts;
+        match last_attempt {
+            true => error!(url = webhook.url, attempt, error, "webhook delivery failed"),
+            false => warn!(
+                url = webhook.url,
+                attempt, error, "webhook delivery attempt failed"
+            ),
+        }
+        update_delivery(&log, id, |delivery| {
+            delivery.attempts = attempt;
+            delivery.response_status = response_status;
+            delivery.error = Some(error);
+            if last_attempt {
+                delivery.status = DeliveryStatus::Failed;
+            }
+        });
+        if !last_attempt {
+            sleep(backoff).await;
+            backoff *= 2;
+        }
+    }
+}
+
+pub fn start_webhooks(
+    webhooks: Vec<WebhookConfig>,
+    replication: Replication,
+    event_sender: Sender<LogEvent>,
+    data_channels: Vec<Sender<Value>>,
+) -> DeliveryLog {
+    let log = DeliveryLog::from(vec![]);
+    if webhooks.is_empty() {
+        return log;
+    }
+    let client = reqwest::Client::new();
+    let mut recv = event_sender.subscribe();
+    let delivery_log = log.clone();
+    tokio::spawn(async move {
+        loop {
+            let event = match recv.recv().await {
+                Ok(event) => event,
+                Err(RecvError::Lagged(skipped)) => {
+                    METRICS.record_lag("webhooks", skipped);
+                    continue;
+                }
+                Err(RecvError::Closed) => break,
+            };
+            // The primary delivers these; a secondary only mirrors its events.
+            if replication.is_secondary() {
+                continue;
+            }
+            let mut data = None;
+            for webhook in &webhooks {
+                for trigger in &webhook.triggers {
+                    if !trigger.pattern.matches(&event) {
+                        continue;
+                    }
+                    if data.is_none() {
+                        data = Some(get_data(&data_channels).await);
+                    }
+                    let data = data.as_ref().unwrap();
+                    if !trigger.matches(&event, data) {
+                        continue;
+                    }
+                    let id = Uuid::new_v4();
+                    let body = json!({
+                        "id": id,
+                        "trigger": trigger.name,
+                        "event": event,
+                        "data": data,
+                    });
+                    {
+                        let mut log = delivery_log.data.lock().unwrap();
+                        if log.len() >= DELIVERY_LOG_SIZE {
+                            log.remove(0);
+                        }
+                        log.push(Delivery {
+                            id,
+                            url: webhook.url.clone(),
+                            trigger: trigger.name.clone(),
+                            log_id: event.log_id,
+                            created: SystemTime::now(),
+                            attempts: 0,
+                            status: DeliveryStatus::Pending,
+                            response_status: None,
+                            error: None,
+                        });
+                    }
+                    let span = info_span!(
+                        "webhook",
+                        delivery = %id,
+                        log_id = %event.log_id,
+                        trigger = trigger.name,
+                    );
+                    let delivery = deliver(
+                        client.clone(),
+                        webhook.clone(),
+                        body.to_string().into_bytes(),
+                        id,
+                        delivery_log.clone(),
+                    );
+                    tokio::spawn(delivery.instrument(span));
+                }
+            }
+        }
+    });
+    log
+}
+
+#[cfg(test)]
+mod tests {
+    use rocket::tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::{TcpListener, TcpStream},
+        sync::broadcast,
+        time::timeout,
+    };
+
+    use super::*;
+    use crate::{
+        component::{Component, TeamComponent},
+        event::{states::CounterEvent, Event},
+        replication::ReplicationConfig,
+    };
+
+    #[test]
+    fn signs_with_hmac_sha256() {
+        assert_eq!(
+            sign("key", b"The quick brown fox jumps over the lazy dog"),
+            "sha256=f7bc83f430538424b13298e6aa6fb143ef4d59a14946175997479dbc2d1a3cd8"
+        );
+    }
+
+    /// Reads one request, returning its lowercased headers and body.
+    async fn read_request(stream: &mut TcpStream) -> (String, Vec<u8>) {
+        let mut request = vec![];
+        let end = loop {
+            let mut buffer = [0; 1024];
+            let read = stream.read(&mut buffer).await.unwrap();
+            assert!(read > 0, "connection closed mid-request");
+            request.extend_from_slice(&buffer[..read]);
+            if let Some(end) = request.windows(4).position(|window| window == b"\r\n\r\n") {
+                break end + 4;
+            }
+        };
+        let headers = String::from_utf8_lossy(&request[..end]).to_ascii_lowercase();
+        let length: usize = headers
+            .lines()
+            .find_map(|line| line.strip_prefix("content-length:"))
+            .map_or(0, |length| length.trim().parse().unwrap());
+        let mut body = request.split_off(end);
+        while body.len() < length {
+            let mut buffer = [0; 1024];
+            let read = stream.read(&mut buffer).await.unwrap();
+            body.extend_from_slice(&buffer[..read]);
+        }
+        (headers, body)
+    }
+
+    async fn respond(listener: &TcpListener, status: &str) -> (String, Vec<u8>) {
+        let (mut stream, _) = timeout(Duration::from_secs(5), listener.accept())
+            .await
+            .unwrap()
+            .unwrap();
+        let request = read_request(&mut stream).await;
+        let response =
+            format!("HTTP/1.1 {status}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n");
+        stream.write_all(response.as_bytes()).await.unwrap();
+        request
+    }
+
+    fn goal_webhook(listener: &TcpListener) -> WebhookConfig {
+        WebhookConfig {
+            url: format!("http://{}/hook", listener.local_addr().unwrap()),
+            secret: Some("shh".into()),
+            triggers: vec![Trigger {
+                name: "goal".into(),
+                pattern: EventPattern {
+                    component: Some("home/score".into()),
+                    kind: Some("increment".into()),
+                },
+                period: None,
+            }],
+            max_attempts: 3,
+            backoff_ms: 10,
+            timeout_ms: default_timeout_ms(),
+        }
+    }
+
+    #[rocket::async_test]
+    async fn retries_signed_deliveries_until_accepted() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let webhook = goal_webhook(&listener);
+        let (event_sender, _) = broadcast::channel(16);
+        let log = start_webhooks(
+            vec![webhook],
+            Replication::new(ReplicationConfig::default()),
+            event_sender.clone(),
+            vec![],
+        );
+        let score = Component::Home(TeamComponent::Score);
+        for event in [CounterEvent::Decrement, CounterEvent::Increment] {
+            event_sender
+                .send(LogEvent::new_now(score, Event::Counter(event)))
+                .unwrap();
+        }
+
+        let (_, first) = respond(&listener, "503 Service Unavailable").await;
+        let (headers, body) = respond(&listener, "200 OK").await;
+        assert_eq!(first, body, "retries resend the same body");
+        let signature = format!("x-scoreboard-signature: {}", sign("shh", &body));
+        assert!(headers.contains(&signature), "{headers}");
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["trigger"], "goal");
+        assert_eq!(body["event"]["event"]["Counter"], "Increment");
+
+        timeout(Duration::from_secs(5), async {
+            while !matches!(
+                log.data.lock().unwrap()[..],
+                [Delivery {
+                    attempts: 2,
+                    status: DeliveryStatus::Delivered,
+                    ..
+                }]
+            ) {
+                sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("delivery logged as delivered on the second attempt");
+        assert_eq!(log.data.lock().unwrap()[0].response_status, Some(200));
+    }
+
+    #[rocket::async_test]
+    async fn secondaries_leave_delivery_to_the_primary() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let replication = Replication::new(ReplicationConfig {
+            primary: Some("ws://127.0.0.1:1/replication/stream".into()),
+            ..Default::default()
+        });
+        let (event_sender, _) = broadcast::channel(16);
+        let log = start_webhooks(
+            vec![goal_webhook(&listener)],
+            replication.clone(),
+            event_sender.clone(),
+            vec![],
+        );
+        let goal = || {
+            LogEvent::new_now(
+                Component::Home(TeamComponent::Score),
+                Event::Counter(CounterEvent::Increment),
+            )
+        };
+
+        event_sender.send(goal()).unwrap();
+        assert!(
+            timeout(Duration::from_millis(200), listener.accept())
+                .await
+                .is_err(),
+            "a secondary delivered a webhook"
+        );
+        assert!(log.data.lock().unwrap().is_empty());
+
+        replication.promote();
+        event_sender.send(goal()).unwrap();
+        let (_, body) = respond(&listener, "200 OK").await;
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["trigger"], "goal");
+    }
+}
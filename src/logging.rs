@@ -0,0 +1,178 @@
+use std::{io::IsTerminal, path::PathBuf, time::Instant};
+
+use rocket::{
+    fairing::{Fairing, Info, Kind},
+    Data, Request, Response,
+};
+use serde::Deserialize;
+use tracing::{info, info_span, Span};
+use tracing_appender::{
+    non_blocking::WorkerGuard,
+    rolling::{RollingFileAppender, Rotation},
+};
+use tracing_subscriber::{
+    fmt::{self, MakeWriter},
+    layer::SubscriberExt,
+    util::SubscriberInitExt,
+    EnvFilter, Layer, Registry,
+};
+use uuid::Uuid;
+
+/// The `logging` section of the Rocket config.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct LoggingConfig {
+    /// Filter directives, e.g. `info` or `backend=debug,rocket=warn`.
+    /// `RUST_LOG` takes precedence when set.
+    pub level: String,
+    pub format: LogFormat,
+    pub file: Option<LogFileConfig>,
+}
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        Self {
+            level: "info".into(),
+            format: LogFormat::Pretty,
+            file: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LogFormat {
+    /// Human readable lines, coloured on a terminal.
+    #[default]
+    Pretty,
+    /// One JSON object per line, including the current spans.
+    Json,
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LogRotation {
+    Hourly,
+    #[default]
+    Daily,
+    Never,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct LogFileConfig {
+    pub directory: PathBuf,
+    #[serde(default = "default_prefix")]
+    pub prefix: String,
+    #[serde(default)]
+    pub rotation: LogRotation,
+    /// Rotated files kept before the oldest is deleted.
+    #[serde(default = "default_max_files")]
+    pub max_files: usize,
+    /// Defaults to the console format.
+    pub format: Option<LogFormat>,
+}
+
+fn default_prefix() -> String {
+    "scoreboard.log".into()
+}
+fn default_max_files() -> usize {
+    14
+}
+
+fn filter(level: &str) -> EnvFilter {
+    EnvFilter::try_from_default_env()
+        .or_else(|_| EnvFilter::try_new(level))
+        .unwrap_or_else(|e| panic!("invalid `logging.level`: {e}"))
+}
+
+type BoxedLayer = Box<dyn Layer<Registry> + Send + Sync>;
+
+fn layer<W>(format: LogFormat, writer: W, ansi: bool, level: &str) -> BoxedLayer
+where
+    W: for<'w> MakeWriter<'w> + Send + Sync + 'static,
+{
+    let layer = fmt::layer().with_writer(writer).with_ansi(ansi);
+    match format {
+        LogFormat::Pretty => layer.with_filter(filter(level)).boxed(),
+        LogFormat::Json => layer
+            .json()
+            .with_span_list(true)
+            .with_filter(filter(level))
+            .boxed(),
+    }
+}
+
+/// Installs the global subscriber. Rocket keeps its own launch and
+/// request output on stdout.
+pub fn init_logging(config: &LoggingConfig) -> LogGuard {
+    let ansi = std::io::stdout().is_terminal();
+    let mut layers = vec![layer(config.format, std::io::stdout, ansi, &config.level)];
+    let mut guard = None;
+    if let Some(file) = &config.file {
+        let rotation = match file.rotation {
+            LogRotation::Hourly => Rotation::HOURLY,
+            LogRotation::Daily => Rotation::DAILY,
+            LogRotation::Never => Rotation::NEVER,
+        };
+        let appender = RollingFileAppender::builder()
+            .rotation(rotation)
+            .filename_prefix(&file.prefix)
+            .max_log_files(file.max_files)
+            .build(&file.directory)
+            .unwrap_or_else(|e| panic!("invalid `logging.file`: {e}"));
+        let (writer, file_guard) = tracing_appender::non_blocking(appender);
+        let format = file.format.unwrap_or(config.format);
+        layers.push(layer(format, writer, false, &config.level));
+        guard = Some(file_guard);
+    }
+    tracing_subscriber::registry().with(layers).init();
+    LogGuard { _file: guard }
+}
+
+/// Flushes the log file when dropped, so it is kept in Rocket's state.
+pub struct LogGuard {
+    _file: Option<WorkerGuard>,
+}
+
+/// Wraps each request in a `request` span and logs its outcome.
+pub struct RequestLogger;
+
+struct RequestSpan {
+    span: Span,
+    started: Instant,
+}
+
+#[rocket::async_trait]
+impl Fairing for RequestLogger {
+    fn info(&self) -> Info {
+        Info {
+            name: "Log requests",
+            kind: Kind::Request | Kind::Response,
+        }
+    }
+
+    async fn on_request(&self, request: &mut Request<'_>, _: &mut Data<'_>) {
+        let span = info_span!(
+            "request",
+            id = %Uuid::new_v4(),
+            method = %request.method(),
+            uri = %request.uri(),
+        );
+        request.local_cache(|| RequestSpan {
+            span,
+            started: Instant::now(),
+        });
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        let request_span = request.local_cache(|| RequestSpan {
+            span: Span::none(),
+            started: Instant::now(),
+        });
+        let _enter = request_span.span.enter();
+        info!(
+            status = response.status().code,
+            elapsed = ?request_span.started.elapsed(),
+            "handled request"
+        );
+    }
+}
@@ -1,33 +1,86 @@
 #[macro_use]
 extern crate rocket;
 
+mod assets;
 mod component;
+mod display;
 mod event;
-// mod scoreboard;
-use std::time::Duration;
+mod fouls;
+mod integration;
+mod logging;
+mod metrics;
+mod replay;
+mod replication;
+mod rules;
+mod scoreboard;
+mod siren;
+mod ticker;
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::PathBuf,
+    time::{Duration, Instant},
+};
 
+use assets::{Asset, AssetFile, Assets, AssetsConfig};
 use component::{
     clock::{GameClock, GameDependentClock, StoppageClock},
-    counter::{Counter, TeamFoulCounter},
-    label::Label,
+    counter::Counter,
+    label::{start_computed_labels, Label},
+    possession::{Possession, PossessionConfig},
     toggle::{Siren, Toggle},
-    Component, GlobalComponent, TeamComponent,
+    BoundsConfig, Component, GlobalComponent, TeamComponent,
+};
+use display::{
+    start_display, ActiveDisplay, DisplayConfig, DisplayError, DisplaySettings, Layout, Theme,
+};
+use event::states::{CounterEvent, LabelEvent, PossessionEvent, ToggleEvent};
+use event::{states::ClockEvent, DataRefresh, Event, EventSource, LogEvent, Shareable};
+use fouls::{start_team_fouls, FoulProfile, FoulStatus, TeamFouls, TeamFoulsConfig};
+use integration::{
+    companion::{all_feedback, companion_command, feedback, Feedback, FeedbackColors},
+    console::{start_console_inputs, ConsoleInputConfig},
+    feed::{feed_fields, start_text_files, to_csv, to_vmix_json, to_xml, TextFilesConfig},
+    midi::{start_midi, Midi, MidiAction, MidiBinding, MidiConfig},
+    mqtt::{start_mqtt, MqttConfig},
+    osc::{start_osc, OscConfig},
+    serial::{start_serial_outputs, SerialOutputConfig},
+    webhook::{start_webhooks, Delivery, DeliveryLog, WebhookConfig},
+    IntegrationSender,
+};
+use logging::{init_logging, LoggingConfig, RequestLogger};
+use metrics::METRICS;
+use replay::{RecordedEvent, Replay, MAX_SPEED, MIN_SPEED};
+use replication::{
+    serve_secondary, start_secondary, Operator, Replication, ReplicationConfig, ReplicationStatus,
 };
-use event::states::{CounterEvent, LabelEvent, ToggleEvent};
-use event::{states::ClockEvent, Event, LogEvent};
 use rocket::{
+    data::{Data, ToByteUnit},
     fairing::{Fairing, Info, Kind},
     fs::FileServer,
-    futures::SinkExt,
-    http::Header,
+    futures::{SinkExt, StreamExt},
+    http::{ContentType, Header, Status},
+    response::{
+        content::{RawJson, RawXml},
+        status::BadRequest,
+        stream::ByteStream,
+    },
+    serde::json::Json,
     tokio::{
         self,
         sync::broadcast::{self, error::RecvError, Sender},
         time::sleep,
     },
-    Request, Response, State,
+    Build, Request, Response, Rocket, State,
+};
+use rules::{start_rules, Rule, RuleStatus, Rules};
+use scoreboard::{
+    start_event_logger, ComponentState, EventPage, EventQuery, ScoreTypeTotal, Scoreboard, Snapshot,
 };
+use serde::de::DeserializeOwned;
 use serde_json::{Map, Value};
+use siren::{Horn, Horns, SirenConfig};
+use ticker::{start_ticker, Ticker, TickerConfig, TickerContent, TickerItem, TickerStatus};
+use tracing::{error, info_span, warn, Instrument};
 use ws::Message;
 
 #[get("/")]
@@ -35,7 +88,8 @@ fn index() -> &'static str {
     "Hello, world!"
 }
 
-async fn get_data(sender: &State<Vec<Sender<Value>>>) -> String {
+async fn get_data(sender: &[Sender<Value>]) -> Value {
+    let started = Instant::now();
     let mut data_map = Map::<String, Value>::default();
     for channel in sender.iter() {
         let mut recv = channel.subscribe();
@@ -51,14 +105,39 @@ async fn get_data(sender: &State<Vec<Sender<Value>>>) -> String {
         let Value::Object(data) = message else {
             panic!("object data not received, go {message:?}");
         };
-        data_map.extend(data.into_iter());
+        data_map.extend(data);
     }
-    serde_json::Value::Object(data_map).to_string()
+    METRICS.observe_get_data(started.elapsed());
+    serde_json::Value::Object(data_map)
+}
+
+#[get("/metrics")]
+fn prometheus_metrics() -> (ContentType, String) {
+    let content_type = ContentType::new("text", "plain").with_params(("version", "0.0.4"));
+    (content_type, METRICS.render())
 }
 
 #[get("/data")]
 async fn data(sender: &State<Vec<Sender<Value>>>) -> String {
-    get_data(sender).await
+    get_data(sender).await.to_string()
+}
+
+// Graphics feeds
+
+#[get("/vmix.json")]
+async fn vmix_feed(sender: &State<Vec<Sender<Value>>>) -> RawJson<String> {
+    RawJson(to_vmix_json(&feed_fields(&get_data(sender).await)))
+}
+#[get("/data.xml")]
+async fn xml_feed(sender: &State<Vec<Sender<Value>>>) -> RawXml<String> {
+    RawXml(to_xml(&feed_fields(&get_data(sender).await)))
+}
+#[get("/data.csv")]
+async fn csv_feed(sender: &State<Vec<Sender<Value>>>) -> (ContentType, String) {
+    (
+        ContentType::CSV,
+        to_csv(&feed_fields(&get_data(sender).await)),
+    )
 }
 
 #[get("/data_stream")]
@@ -66,29 +145,586 @@ fn echo_stream<'a>(
     ws: ws::WebSocket,
     event_channel: &'a State<Sender<LogEvent>>,
     data_channels: &'a State<Vec<Sender<Value>>>,
+    refresh: &'a State<DataRefresh>,
 ) -> ws::Channel<'a> {
     let mut recv = event_channel.subscribe();
+    let mut refreshes = refresh.subscribe();
     ws.channel(move |mut stream| {
         Box::pin(async move {
-            let mut last = Message::Text(get_data(data_channels).await);
-            if let e @ Err(_) = stream.send(last.clone()).await {
-                eprintln!("{e:?}");
+            let _client = METRICS.websocket_client("data");
+            let mut last = Message::Text(get_data(data_channels).await.to_string());
+            if let Err(e) = stream.send(last.clone()).await {
+                warn!(error = %e, "failed to send initial data to data stream");
                 return Ok(());
             }
             loop {
-                match recv.recv().await {
-                    Ok(_) => {}
-                    e @ Err(RecvError::Closed) => {
-                        eprintln!("{e:?}");
+                let changed = tokio::select! {
+                    event = recv.recv() => event.map(drop),
+                    refresh = refreshes.recv() => refresh,
+                    // Reading notices clients that went away between events.
+                    message = stream.next() => match message {
+                        Some(Ok(Message::Close(_)) | Err(_)) | None => break,
+                        Some(Ok(_)) => continue,
+                    },
+                };
+                match changed {
+                    Ok(()) => {}
+                    Err(RecvError::Closed) => {
+                        error!("event channel closed, ending data stream");
                         break;
                     }
-                    _ => continue,
+                    // Missed events still changed the data, so refresh anyway.
+                    Err(RecvError::Lagged(skipped)) => METRICS.record_lag("data_stream", skipped),
                 }
-                let data = Message::Text(get_data(data_channels).await);
+                let data = Message::Text(get_data(data_channels).await.to_string());
                 if data != last {
                     last = data.clone();
-                    if let e @ Err(_) = stream.send(data).await {
-                        eprintln!("{e:?}");
+                    if let Err(e) = stream.send(data).await {
+                        warn!(error = %e, "failed to send data to data stream");
+                        break;
+                    };
+                }
+            }
+            Ok(())
+        })
+    })
+}
+
+/// Events exported per lock of the scoreboard.
+const EXPORT_CHUNK: usize = 500;
+
+/// Streams the event log as a JSON array, locking the scoreboard for one
+/// chunk at a time. Events logged once the export started are left out.
+#[get("/events/export")]
+fn export_events(scoreboard: &State<Shareable<Scoreboard>>) -> (ContentType, ByteStream![Vec<u8>]) {
+    let scoreboard = scoreboard.inner().clone();
+    let end = scoreboard.data.lock().unwrap().logged_events();
+    let stream = ByteStream! {
+        yield b"[".to_vec();
+        let (mut next, mut first) = (0, true);
+        while next < end {
+            let (events, reached) = scoreboard
+                .data
+                .lock()
+                .unwrap()
+                .logged_range(next..end.min(next + EXPORT_CHUNK));
+            next = reached;
+            let mut chunk = vec![];
+            for event in events {
+                if !std::mem::take(&mut first) {
+                    chunk.push(b',');
+                }
+                serde_json::to_writer(&mut chunk, &event).expect("event serializes");
+            }
+            yield chunk;
+        }
+        yield b"]".to_vec();
+    };
+    (ContentType::JSON, stream)
+}
+
+#[get("/events/score_types")]
+fn score_types(
+    scoreboard: &State<Shareable<Scoreboard>>,
+) -> Json<BTreeMap<String, BTreeMap<String, ScoreTypeTotal>>> {
+    Json(scoreboard.data.lock().unwrap().score_types())
+}
+
+#[get("/events?<query..>")]
+fn events(scoreboard: &State<Shareable<Scoreboard>>, query: EventQuery) -> Json<EventPage> {
+    Json(scoreboard.data.lock().unwrap().query_events(&query))
+}
+
+// Control surfaces (Bitfocus Companion)
+
+fn companion_action(
+    sender: &Sender<LogEvent>,
+    scoreboard: &Shareable<Scoreboard>,
+    path: PathBuf,
+    value: Option<&str>,
+) -> Status {
+    let path = path.to_string_lossy();
+    let command = companion_command(&path, value, &scoreboard.data.lock().unwrap());
+    let Some((component, event)) = command else {
+        return Status::NotFound;
+    };
+    let event = LogEvent {
+        source: EventSource::Integration("companion".into()),
+        ..LogEvent::new_now(component, event)
+    };
+    if let Err(e) = scoreboard.data.lock().unwrap().validate(&event) {
+        warn!(error = e, "companion: rejected action");
+        return Status::BadRequest;
+    }
+    sender.send(event).expect("message sent");
+    Status::Ok
+}
+#[get("/action/<path..>?<value>")]
+fn companion_action_get(
+    sender: Operator<'_>,
+    scoreboard: &State<Shareable<Scoreboard>>,
+    path: PathBuf,
+    value: Option<&str>,
+) -> Status {
+    companion_action(&sender, scoreboard, path, value)
+}
+#[post("/action/<path..>?<value>")]
+fn companion_action_post(
+    sender: Operator<'_>,
+    scoreboard: &State<Shareable<Scoreboard>>,
+    path: PathBuf,
+    value: Option<&str>,
+) -> Status {
+    companion_action(&sender, scoreboard, path, value)
+}
+#[get("/feedback")]
+async fn companion_feedbacks(sender: &State<Vec<Sender<Value>>>) -> Json<Map<String, Value>> {
+    Json(all_feedback(&get_data(sender).await))
+}
+#[get("/feedback/<key>?<on>&<off>")]
+async fn companion_feedback(
+    sender: &State<Vec<Sender<Value>>>,
+    key: &str,
+    on: Option<String>,
+    off: Option<String>,
+) -> Option<Json<Feedback>> {
+    let data = get_data(sender).await;
+    let value = data.get(key)?;
+    Some(Json(feedback(value, &FeedbackColors { on, off })))
+}
+
+// Rules
+
+#[get("/")]
+fn list_rules(rules: &State<Rules>) -> Json<Vec<RuleStatus>> {
+    Json(rules.list())
+}
+#[get("/<name>")]
+fn get_rule(rules: &State<Rules>, name: &str) -> Option<Json<RuleStatus>> {
+    rules.get(name).map(Json)
+}
+#[post("/<name>/enable")]
+fn enable_rule(rules: &State<Rules>, name: &str) -> Status {
+    match rules.set_enabled(name, true) {
+        true => Status::Ok,
+        false => Status::NotFound,
+    }
+}
+#[post("/<name>/disable")]
+fn disable_rule(rules: &State<Rules>, name: &str) -> Status {
+    match rules.set_enabled(name, false) {
+        true => Status::Ok,
+        false => Status::NotFound,
+    }
+}
+
+// Team fouls
+
+#[get("/")]
+fn foul_status(fouls: &State<TeamFouls>) -> Json<FoulStatus> {
+    Json(fouls.status())
+}
+#[get("/profiles")]
+fn foul_profiles(fouls: &State<TeamFouls>) -> Json<BTreeMap<String, FoulProfile>> {
+    Json(fouls.profiles().clone())
+}
+#[post("/profile/<name>")]
+fn set_foul_profile(fouls: &State<TeamFouls>, name: &str) -> Status {
+    match fouls.set_profile(name) {
+        true => Status::Ok,
+        false => Status::NotFound,
+    }
+}
+
+// Ticker
+
+#[get("/")]
+fn ticker_status(ticker: &State<Ticker>) -> Json<TickerStatus> {
+    Json(ticker.status())
+}
+#[get("/items")]
+fn ticker_items(ticker: &State<Ticker>) -> Json<Vec<TickerItem>> {
+    Json(ticker.items())
+}
+#[put("/items", data = "<items>")]
+fn set_ticker_items(
+    ticker: &State<Ticker>,
+    items: Json<Vec<TickerItem>>,
+) -> Result<(), BadRequest<String>> {
+    ticker.set_items(items.into_inner()).map_err(BadRequest)
+}
+#[post("/items", data = "<item>")]
+fn push_ticker_item(
+    ticker: &State<Ticker>,
+    item: Json<TickerItem>,
+) -> Result<(), BadRequest<String>> {
+    ticker.push(item.into_inner()).map_err(BadRequest)
+}
+#[delete("/items/<index>")]
+fn remove_ticker_item(ticker: &State<Ticker>, index: usize) -> Option<Json<TickerItem>> {
+    ticker.remove(index).map(Json)
+}
+#[post("/urgent", data = "<item>")]
+fn interrupt_ticker(
+    ticker: &State<Ticker>,
+    item: Json<TickerItem>,
+) -> Result<(), BadRequest<String>> {
+    ticker.interrupt(item.into_inner()).map_err(BadRequest)
+}
+#[delete("/urgent")]
+fn clear_urgent_ticker(ticker: &State<Ticker>) -> Status {
+    match ticker.clear_urgent() {
+        true => Status::Ok,
+        false => Status::NotFound,
+    }
+}
+
+// Assets
+
+#[get("/")]
+fn list_assets(assets: &State<Assets>) -> Json<Vec<Asset>> {
+    Json(assets.list())
+}
+#[get("/<id>")]
+async fn get_asset(assets: &State<Assets>, id: &str) -> Option<AssetFile> {
+    assets.open(&assets.get(id)?).await
+}
+#[post("/?<name>", data = "<data>")]
+async fn upload_asset(
+    assets: &State<Assets>,
+    name: Option<String>,
+    data: Data<'_>,
+) -> Result<Json<Asset>, (Status, String)> {
+    let bytes = data
+        .open(assets.max_size().bytes())
+        .into_bytes()
+        .await
+        .map_err(|e| (Status::BadRequest, e.to_string()))?;
+    if !bytes.is_complete() {
+        let limit = assets.max_size();
+        return Err((
+            Status::PayloadTooLarge,
+            format!("assets can be at most {limit} bytes"),
+        ));
+    }
+    let Some(media_type) = assets.accepts(&bytes) else {
+        return Err((
+            Status::UnsupportedMediaType,
+            "uploads must be images of an accepted type".into(),
+        ));
+    };
+    match assets.save(media_type.into(), name, &bytes) {
+        Ok(asset) => Ok(Json(asset)),
+        Err(e) => {
+            error!(error = %e, "failed to save asset");
+            Err((Status::InternalServerError, "failed to save asset".into()))
+        }
+    }
+}
+/// Why an asset can't be deleted, if it is shown as a logo or in the ticker.
+fn asset_in_use(id: &str, scoreboard: &Scoreboard, ticker: &Ticker) -> Option<String> {
+    let logos = [
+        Component::Home(TeamComponent::Logo),
+        Component::Away(TeamComponent::Logo),
+    ];
+    let logo = logos.into_iter().any(|logo| match scoreboard.find(logo) {
+        Some(ComponentState::Label(label)) => label.value() == id,
+        _ => false,
+    });
+    if logo {
+        return Some(format!("asset {id} is in use as a logo"));
+    }
+    let shown = |item: &TickerItem| match &item.content {
+        TickerContent::Image(image) => image == id || image.ends_with(&format!("/{id}")),
+        TickerContent::Text(_) => false,
+    };
+    let status = ticker.status();
+    let urgent = status.item.filter(|_| status.urgent);
+    if ticker.items().iter().chain(&urgent).any(shown) {
+        return Some(format!("asset {id} is in the ticker"));
+    }
+    None
+}
+/// Refuses to delete a logo or ticker image that is in use.
+#[delete("/<id>")]
+fn delete_asset(
+    assets: &State<Assets>,
+    scoreboard: &State<Shareable<Scoreboard>>,
+    ticker: &State<Ticker>,
+    id: &str,
+) -> Result<(), (Status, String)> {
+    let in_use = asset_in_use(id, &scoreboard.data.lock().unwrap(), ticker);
+    if let Some(reason) = in_use {
+        return Err((Status::Conflict, reason));
+    }
+    match assets.delete(id) {
+        Ok(true) => Ok(()),
+        Ok(false) => Err((Status::NotFound, format!("there is no asset {id}"))),
+        Err(e) => {
+            error!(error = %e, "failed to delete asset");
+            Err((Status::InternalServerError, "failed to delete asset".into()))
+        }
+    }
+}
+
+// Display layouts and themes
+
+/// Maps failed layout and theme changes to responses.
+fn display_result(result: Result<(), DisplayError>) -> Result<(), (Status, String)> {
+    result.map_err(|e| match e {
+        DisplayError::NotFound => (Status::NotFound, "no such layout or theme".into()),
+        DisplayError::Active => (Status::Conflict, "can't delete what is active".into()),
+        DisplayError::Invalid(e) => (Status::BadRequest, e),
+        DisplayError::Io(e) => {
+            error!(error = %e, "failed to save display settings");
+            (
+                Status::InternalServerError,
+                "failed to save display settings".into(),
+            )
+        }
+    })
+}
+#[get("/")]
+fn active_display(display: &State<DisplaySettings>) -> Json<ActiveDisplay> {
+    Json(display.active())
+}
+#[get("/layouts")]
+fn display_layouts(display: &State<DisplaySettings>) -> Json<BTreeMap<String, Layout>> {
+    Json(display.layouts())
+}
+#[put("/layouts/<name>", data = "<layout>")]
+fn set_display_layout(
+    display: &State<DisplaySettings>,
+    name: &str,
+    layout: Json<Layout>,
+) -> Result<(), (Status, String)> {
+    display_result(display.set_layout(name, layout.into_inner()))
+}
+#[delete("/layouts/<name>")]
+fn delete_display_layout(
+    display: &State<DisplaySettings>,
+    name: &str,
+) -> Result<(), (Status, String)> {
+    display_result(display.delete_layout(name))
+}
+#[post("/layouts/<name>/activate")]
+fn activate_display_layout(
+    display: &State<DisplaySettings>,
+    name: &str,
+) -> Result<(), (Status, String)> {
+    display_result(display.activate_layout(name))
+}
+#[get("/themes")]
+fn display_themes(display: &State<DisplaySettings>) -> Json<BTreeMap<String, Theme>> {
+    Json(display.themes())
+}
+#[put("/themes/<name>", data = "<theme>")]
+fn set_display_theme(
+    display: &State<DisplaySettings>,
+    name: &str,
+    theme: Json<Theme>,
+) -> Result<(), (Status, String)> {
+    display_result(display.set_theme(name, theme.into_inner()))
+}
+#[delete("/themes/<name>")]
+fn delete_display_theme(
+    display: &State<DisplaySettings>,
+    name: &str,
+) -> Result<(), (Status, String)> {
+    display_result(display.delete_theme(name))
+}
+#[post("/themes/<name>/activate")]
+fn activate_display_theme(
+    display: &State<DisplaySettings>,
+    name: &str,
+) -> Result<(), (Status, String)> {
+    display_result(display.activate_theme(name))
+}
+
+// Siren
+
+#[get("/patterns")]
+fn siren_patterns(horns: &State<Horns>) -> Json<BTreeMap<String, Vec<u64>>> {
+    Json(horns.patterns().clone())
+}
+#[post("/<horn>/pattern/<name>")]
+fn play_siren_pattern(
+    _operator: Operator<'_>,
+    horns: &State<Horns>,
+    horn: Horn,
+    name: &str,
+) -> Status {
+    match horns.play(horn, name) {
+        true => Status::Ok,
+        false => Status::NotFound,
+    }
+}
+#[post("/<horn>/press")]
+fn press_horn(_operator: Operator<'_>, horns: &State<Horns>, horn: Horn) {
+    horns.press(horn);
+}
+#[post("/<horn>/release")]
+fn release_horn(_operator: Operator<'_>, horns: &State<Horns>, horn: Horn) {
+    horns.release(horn);
+}
+
+// MIDI controllers
+
+#[get("/mapping")]
+fn midi_mapping(midi: &State<Midi>) -> Json<Vec<MidiBinding>> {
+    Json(midi.mapping())
+}
+#[put("/mapping", data = "<mapping>")]
+fn set_midi_mapping(midi: &State<Midi>, mapping: Json<Vec<MidiBinding>>) -> Status {
+    match midi.set_mapping(mapping.into_inner()) {
+        Ok(()) => Status::Ok,
+        Err(e) => {
+            error!(error = %e, "failed to save midi mapping");
+            Status::InternalServerError
+        }
+    }
+}
+/// Waits for the next pad or knob to be pressed and binds it to `action`.
+#[post("/learn?<timeout_ms>", data = "<action>")]
+async fn learn_midi(
+    midi: &State<Midi>,
+    action: Json<MidiAction>,
+    timeout_ms: Option<u64>,
+) -> Result<Json<MidiBinding>, Status> {
+    let wait = Duration::from_millis(timeout_ms.unwrap_or(10_000));
+    let binding = midi.learn(action.into_inner(), wait).await;
+    binding.map(Json).ok_or(Status::RequestTimeout)
+}
+
+// Snapshots
+
+#[get("/")]
+fn snapshot(scoreboard: &State<Shareable<Scoreboard>>) -> Json<Snapshot> {
+    Json(scoreboard.data.lock().unwrap().snapshot())
+}
+#[post("/?<ts>", data = "<snapshot>")]
+fn restore_snapshot(sender: Operator<'_>, snapshot: Json<Snapshot>, ts: Option<usize>) -> Status {
+    let events = match snapshot
+        .into_inner()
+        .restore_events(ts, EventSource::Operator)
+    {
+        Ok(events) => events,
+        Err(e) => {
+            warn!(error = e, "rejected snapshot");
+            return Status::UnprocessableEntity;
+        }
+    };
+    for event in events {
+        sender.send(event).expect("message sent");
+    }
+    Status::Ok
+}
+
+// Replication
+
+#[get("/")]
+fn replication_status(replication: &State<Replication>) -> Json<ReplicationStatus> {
+    Json(replication.status())
+}
+#[post("/promote")]
+fn promote(replication: &State<Replication>) {
+    replication.promote();
+}
+#[get("/stream")]
+fn replication_stream<'a>(
+    ws: ws::WebSocket,
+    event_channel: &'a State<Sender<LogEvent>>,
+    scoreboard: &'a State<Shareable<Scoreboard>>,
+    replication: &'a State<Replication>,
+) -> ws::Channel<'a> {
+    let heartbeat = Duration::from_millis(replication.config.heartbeat_interval_ms);
+    ws.channel(move |stream| {
+        Box::pin(async move {
+            let _client = METRICS.websocket_client("replication");
+            serve_secondary(
+                stream,
+                scoreboard.inner().clone(),
+                event_channel.inner().clone(),
+                heartbeat,
+            )
+            .await;
+            Ok(())
+        })
+    })
+}
+
+// Webhooks
+
+#[get("/deliveries")]
+fn webhook_deliveries(deliveries: &State<DeliveryLog>) -> Json<Vec<Delivery>> {
+    Json(deliveries.data.lock().unwrap().clone())
+}
+
+// Replay
+
+#[post("/?<speed>&<step>", data = "<recorded>")]
+fn start_replay(
+    replay: &State<Shareable<Replay>>,
+    recorded: Json<Vec<RecordedEvent>>,
+    speed: Option<f64>,
+    step: Option<bool>,
+) -> Result<(), BadRequest<String>> {
+    let speed = match (step, speed.unwrap_or(1.0)) {
+        (Some(true), _) => None,
+        (_, speed) if speed.is_finite() => Some(speed.clamp(MIN_SPEED, MAX_SPEED)),
+        (_, speed) => return Err(BadRequest(format!("invalid replay speed {speed}"))),
+    };
+    replay
+        .data
+        .lock()
+        .unwrap()
+        .start(recorded.into_inner(), speed);
+    Ok(())
+}
+#[post("/step")]
+fn step_replay(replay: &State<Shareable<Replay>>) -> Status {
+    if replay.data.lock().unwrap().step() {
+        Status::Ok
+    } else {
+        Status::Conflict
+    }
+}
+#[post("/stop")]
+fn stop_replay(replay: &State<Shareable<Replay>>) {
+    replay.data.lock().unwrap().stop();
+}
+#[get("/data")]
+fn replay_data(replay: &State<Shareable<Replay>>) -> Option<String> {
+    let data = replay.data.lock().unwrap().get_data()?;
+    Some(data.to_string())
+}
+#[get("/data_stream")]
+fn replay_stream<'a>(ws: ws::WebSocket, replay: &'a State<Shareable<Replay>>) -> ws::Channel<'a> {
+    let (mut recv, current) = {
+        let replay = replay.data.lock().unwrap();
+        (replay.data_channel().subscribe(), replay.get_data())
+    };
+    ws.channel(move |mut stream| {
+        Box::pin(async move {
+            let _client = METRICS.websocket_client("replay");
+            let mut last = Message::Text(current.unwrap_or(Value::Null).to_string());
+            if let Err(e) = stream.send(last.clone()).await {
+                warn!(error = %e, "failed to send initial data to replay stream");
+                return Ok(());
+            }
+            loop {
+                let data = match recv.recv().await {
+                    Ok(data) => Message::Text(data.to_string()),
+                    Err(RecvError::Closed) => {
+                        error!("replay data channel closed, ending replay stream");
+                        break;
+                    }
+                    _ => continue,
+                };
+                if data != last {
+                    last = data.clone();
+                    if let Err(e) = stream.send(data).await {
+                        warn!(error = %e, "failed to send data to replay stream");
                         break;
                     };
                 }
@@ -99,7 +735,7 @@ fn echo_stream<'a>(
 }
 
 #[post("/reset?<ts>&<uuid>")]
-fn reset(sender: &State<Sender<LogEvent>>, ts: Option<usize>, uuid: Option<String>) {
+fn reset(sender: Operator<'_>, ts: Option<usize>, uuid: Option<String>) {
     sender
         .send(LogEvent::new(Component::All, Event::Reset, ts, uuid))
         .expect("message sent");
@@ -109,198 +745,210 @@ fn reset(sender: &State<Sender<LogEvent>>, ts: Option<usize>, uuid: Option<Strin
 
 #[post("/<target>/<clock_event>?<value>&<ts>&<uuid>")]
 fn global_clock_event(
-    sender: &State<Sender<LogEvent>>,
+    sender: Operator<'_>,
+    scoreboard: &State<Shareable<Scoreboard>>,
     target: GlobalComponent,
     clock_event: ClockEvent,
     value: Option<u64>,
     ts: Option<usize>,
     uuid: Option<String>,
-) {
+) -> Result<(), BadRequest<String>> {
     clock_event_handler(
-        sender,
+        &sender,
+        scoreboard,
         Component::Global(target),
         clock_event,
         value,
         ts,
         uuid,
-    );
+    )
 }
 #[post("/home/<target>/<clock_event>?<value>&<ts>&<uuid>")]
 fn home_clock_event(
-    sender: &State<Sender<LogEvent>>,
+    sender: Operator<'_>,
+    scoreboard: &State<Shareable<Scoreboard>>,
     target: TeamComponent,
     clock_event: ClockEvent,
     value: Option<u64>,
     ts: Option<usize>,
     uuid: Option<String>,
-) {
+) -> Result<(), BadRequest<String>> {
     clock_event_handler(
-        sender,
+        &sender,
+        scoreboard,
         Component::Home(target),
         clock_event,
         value,
         ts,
         uuid,
-    );
+    )
 }
 #[post("/away/<target>/<clock_event>?<value>&<ts>&<uuid>")]
 fn away_clock_event(
-    sender: &State<Sender<LogEvent>>,
+    sender: Operator<'_>,
+    scoreboard: &State<Shareable<Scoreboard>>,
     target: TeamComponent,
     clock_event: ClockEvent,
     value: Option<u64>,
     ts: Option<usize>,
     uuid: Option<String>,
-) {
+) -> Result<(), BadRequest<String>> {
     clock_event_handler(
-        sender,
+        &sender,
+        scoreboard,
         Component::Away(target),
         clock_event,
         value,
         ts,
         uuid,
-    );
+    )
 }
 fn clock_event_handler(
-    sender: &State<Sender<LogEvent>>,
+    sender: &Sender<LogEvent>,
+    scoreboard: &State<Shareable<Scoreboard>>,
     target: Component,
     mut clock_event: ClockEvent,
     value: Option<u64>,
     ts: Option<usize>,
     uuid: Option<String>,
-) {
+) -> Result<(), BadRequest<String>> {
     if !target.is_clock() {
         panic!("{target:?} is not a clock component");
     };
 
-    clock_event = match (clock_event, value) {
-        (ClockEvent::Set(_), Some(ms)) => ClockEvent::Set(Duration::from_millis(ms)),
-        (ClockEvent::Increment(_), Some(ms)) => ClockEvent::Increment(Duration::from_millis(ms)),
-        (ClockEvent::Decrement(_), Some(ms)) => ClockEvent::Decrement(Duration::from_millis(ms)),
-        (ClockEvent::Start(_), Some(ms)) => ClockEvent::Start(Some(Duration::from_millis(ms))),
-        (ClockEvent::Stop(_), Some(ms)) => ClockEvent::Stop(Some(Duration::from_millis(ms))),
-        _ => clock_event,
-    };
+    clock_event = clock_event.with_value(value);
 
-    sender
-        .send(LogEvent::new(target, Event::Clock(clock_event), ts, uuid))
-        .expect("message sent");
+    let event = LogEvent::new(target, Event::Clock(clock_event), ts, uuid);
+    send_checked(sender, scoreboard, event)
+}
+
+/// Sends an operator event unless it breaks the component's limits.
+fn send_checked(
+    sender: &Sender<LogEvent>,
+    scoreboard: &Shareable<Scoreboard>,
+    event: LogEvent,
+) -> Result<(), BadRequest<String>> {
+    scoreboard
+        .data
+        .lock()
+        .unwrap()
+        .validate(&event)
+        .map_err(BadRequest)?;
+    sender.send(event).expect("message sent");
+    Ok(())
 }
 
 // Counters
 
-#[post("/<target>/<counter_event>?<value>&<ts>&<uuid>")]
-fn global_counter_event(
-    sender: &State<Sender<LogEvent>>,
-    target: GlobalComponent,
-    counter_event: CounterEvent,
+#[derive(Debug, FromForm)]
+struct CounterQuery {
     value: Option<u64>,
+    /// Tags the event in the log, e.g. `3pt`.
+    score_type: Option<String>,
     ts: Option<usize>,
     uuid: Option<String>,
-) {
+}
+
+#[post("/<target>/<counter_event>?<query..>")]
+fn global_counter_event(
+    sender: Operator<'_>,
+    scoreboard: &State<Shareable<Scoreboard>>,
+    target: GlobalComponent,
+    counter_event: CounterEvent,
+    query: CounterQuery,
+) -> Result<(), BadRequest<String>> {
     counter_event_handler(
-        sender,
+        &sender,
+        scoreboard,
         Component::Global(target),
         counter_event,
-        value,
-        ts,
-        uuid,
-    );
+        query,
+    )
 }
-#[post("/home/<target>/<counter_event>?<value>&<ts>&<uuid>")]
+#[post("/home/<target>/<counter_event>?<query..>")]
 fn home_counter_event(
-    sender: &State<Sender<LogEvent>>,
+    sender: Operator<'_>,
+    scoreboard: &State<Shareable<Scoreboard>>,
     target: TeamComponent,
     counter_event: CounterEvent,
-    value: Option<u64>,
-    ts: Option<usize>,
-    uuid: Option<String>,
-) {
+    query: CounterQuery,
+) -> Result<(), BadRequest<String>> {
     counter_event_handler(
-        sender,
+        &sender,
+        scoreboard,
         Component::Home(target),
         counter_event,
-        value,
-        ts,
-        uuid,
-    );
+        query,
+    )
 }
-#[post("/away/<target>/<counter_event>?<value>&<ts>&<uuid>")]
+#[post("/away/<target>/<counter_event>?<query..>")]
 fn away_counter_event(
-    sender: &State<Sender<LogEvent>>,
+    sender: Operator<'_>,
+    scoreboard: &State<Shareable<Scoreboard>>,
     target: TeamComponent,
     counter_event: CounterEvent,
-    value: Option<u64>,
-    ts: Option<usize>,
-    uuid: Option<String>,
-) {
+    query: CounterQuery,
+) -> Result<(), BadRequest<String>> {
     counter_event_handler(
-        sender,
+        &sender,
+        scoreboard,
         Component::Away(target),
         counter_event,
-        value,
-        ts,
-        uuid,
-    );
+        query,
+    )
 }
 fn counter_event_handler(
-    sender: &State<Sender<LogEvent>>,
+    sender: &Sender<LogEvent>,
+    scoreboard: &State<Shareable<Scoreboard>>,
     target: Component,
     mut counter_event: CounterEvent,
-    value: Option<u64>,
-    ts: Option<usize>,
-    uuid: Option<String>,
-) {
+    query: CounterQuery,
+) -> Result<(), BadRequest<String>> {
     if !target.is_counter() {
         panic!("{target:?} is not a counter component");
     };
-    if let (CounterEvent::Set(_), Some(val)) = (counter_event, value) {
-        counter_event = CounterEvent::Set(val);
-    }
-    sender
-        .send(LogEvent::new(
-            target,
-            Event::Counter(counter_event),
-            ts,
-            uuid,
-        ))
-        .expect("message sent");
+    counter_event = counter_event.with_value(query.value);
+    let event = LogEvent {
+        score_type: query.score_type,
+        ..LogEvent::new(target, Event::Counter(counter_event), query.ts, query.uuid)
+    };
+    send_checked(sender, scoreboard, event)
 }
 
 // Toggles
 
 #[post("/<target>/<toggle_event>?<ts>&<uuid>")]
 fn global_toggle_event(
-    sender: &State<Sender<LogEvent>>,
+    sender: Operator<'_>,
     target: GlobalComponent,
     toggle_event: ToggleEvent,
     ts: Option<usize>,
     uuid: Option<String>,
 ) {
-    toggle_event_handler(sender, Component::Global(target), toggle_event, ts, uuid);
+    toggle_event_handler(&sender, Component::Global(target), toggle_event, ts, uuid);
 }
 #[post("/home/<target>/<toggle_event>?<ts>&<uuid>")]
 fn home_toggle_event(
-    sender: &State<Sender<LogEvent>>,
+    sender: Operator<'_>,
     target: TeamComponent,
     toggle_event: ToggleEvent,
     ts: Option<usize>,
     uuid: Option<String>,
 ) {
-    toggle_event_handler(sender, Component::Home(target), toggle_event, ts, uuid);
+    toggle_event_handler(&sender, Component::Home(target), toggle_event, ts, uuid);
 }
 #[post("/away/<target>/<toggle_event>?<ts>&<uuid>")]
 fn away_toggle_event(
-    sender: &State<Sender<LogEvent>>,
+    sender: Operator<'_>,
     target: TeamComponent,
     toggle_event: ToggleEvent,
     ts: Option<usize>,
     uuid: Option<String>,
 ) {
-    toggle_event_handler(sender, Component::Away(target), toggle_event, ts, uuid);
+    toggle_event_handler(&sender, Component::Away(target), toggle_event, ts, uuid);
 }
 fn toggle_event_handler(
-    sender: &State<Sender<LogEvent>>,
+    sender: &Sender<LogEvent>,
     target: Component,
     toggle_event: ToggleEvent,
     ts: Option<usize>,
@@ -314,79 +962,118 @@ fn toggle_event_handler(
         .expect("message sent");
 }
 
-// Labels
+// Possession
 
-#[post("/<target>/<label_event>?<value>&<ts>&<uuid>")]
-fn global_label_event(
-    sender: &State<Sender<LogEvent>>,
+#[post("/<target>/<possession_event>?<ts>&<uuid>")]
+fn global_possession_event(
+    sender: Operator<'_>,
     target: GlobalComponent,
-    label_event: LabelEvent,
+    possession_event: PossessionEvent,
+    ts: Option<usize>,
+    uuid: Option<String>,
+) -> Status {
+    let target = Component::Global(target);
+    if !target.is_possession() {
+        return Status::NotFound;
+    }
+    sender
+        .send(LogEvent::new(
+            target,
+            Event::Possession(possession_event),
+            ts,
+            uuid,
+        ))
+        .expect("message sent");
+    Status::Ok
+}
+
+// Labels
+
+#[derive(Debug, FromForm)]
+struct LabelQuery {
     value: Option<String>,
     ts: Option<usize>,
     uuid: Option<String>,
-) {
+}
+
+#[post("/<target>/<label_event>?<query..>")]
+fn global_label_event(
+    sender: Operator<'_>,
+    scoreboard: &State<Shareable<Scoreboard>>,
+    assets: &State<Assets>,
+    target: GlobalComponent,
+    label_event: LabelEvent,
+    query: LabelQuery,
+) -> Result<(), BadRequest<String>> {
     label_event_handler(
-        sender,
+        &sender,
+        scoreboard,
+        assets,
         Component::Global(target),
         label_event,
-        value,
-        ts,
-        uuid,
-    );
+        query,
+    )
 }
-#[post("/home/<target>/<label_event>?<value>&<ts>&<uuid>")]
+#[post("/home/<target>/<label_event>?<query..>")]
 fn home_label_event(
-    sender: &State<Sender<LogEvent>>,
+    sender: Operator<'_>,
+    scoreboard: &State<Shareable<Scoreboard>>,
+    assets: &State<Assets>,
     target: TeamComponent,
     label_event: LabelEvent,
-    value: Option<String>,
-    ts: Option<usize>,
-    uuid: Option<String>,
-) {
+    query: LabelQuery,
+) -> Result<(), BadRequest<String>> {
     label_event_handler(
-        sender,
+        &sender,
+        scoreboard,
+        assets,
         Component::Home(target),
         label_event,
-        value,
-        ts,
-        uuid,
-    );
+        query,
+    )
 }
-#[post("/away/<target>/<label_event>?<value>&<ts>&<uuid>")]
+#[post("/away/<target>/<label_event>?<query..>")]
 fn away_label_event(
-    sender: &State<Sender<LogEvent>>,
+    sender: Operator<'_>,
+    scoreboard: &State<Shareable<Scoreboard>>,
+    assets: &State<Assets>,
     target: TeamComponent,
     label_event: LabelEvent,
-    value: Option<String>,
-    ts: Option<usize>,
-    uuid: Option<String>,
-) {
+    query: LabelQuery,
+) -> Result<(), BadRequest<String>> {
     label_event_handler(
-        sender,
+        &sender,
+        scoreboard,
+        assets,
         Component::Away(target),
         label_event,
-        value,
-        ts,
-        uuid,
-    );
+        query,
+    )
 }
 fn label_event_handler(
-    sender: &State<Sender<LogEvent>>,
+    sender: &Sender<LogEvent>,
+    scoreboard: &State<Shareable<Scoreboard>>,
+    assets: &State<Assets>,
     target: Component,
     mut label_event: LabelEvent,
-    value: Option<String>,
-    ts: Option<usize>,
-    uuid: Option<String>,
-) {
+    query: LabelQuery,
+) -> Result<(), BadRequest<String>> {
     if !target.is_label() {
         panic!("{target:?} is not a label component");
     };
-    if let (LabelEvent::Set(_), Some(val)) = (&label_event, value) {
-        label_event = LabelEvent::Set(val);
+    label_event = label_event.with_value(query.value);
+    // Logos hold the id of an uploaded asset, or nothing.
+    let is_logo = matches!(
+        target,
+        Component::Home(TeamComponent::Logo) | Component::Away(TeamComponent::Logo)
+    );
+    if let (true, LabelEvent::Set(id)) = (is_logo, &label_event) {
+        if !id.is_empty() && assets.get(id).is_none() {
+            return Err(BadRequest(format!("there is no asset {id}")));
+        }
     }
-    sender
-        .send(LogEvent::new(target, Event::Label(label_event), ts, uuid))
-        .expect("message sent");
+    let event = LogEvent::new(target, Event::Label(label_event), query.ts, query.uuid);
+    send_checked(sender, scoreboard, event)
 }
 
 fn create_data_channel<T: Clone>() -> Sender<T> {
@@ -394,11 +1081,14 @@ fn create_data_channel<T: Clone>() -> Sender<T> {
 }
 
 macro_rules! run_components {
-    ($send: expr, $data_channels: expr, $($typ: ident { $($arg: expr),* },)* ) => {
+    ($send: expr, $data_channels: expr, $scoreboard: expr, $($typ: ident { $($arg: expr),* },)* ) => {
         $(
             let data_channel = create_data_channel();
             let component = $typ::new($send.clone(), data_channel.clone(), $($arg),*);
-            tokio::spawn(async move { component.run().await });
+            let mirror = component.mirror();
+            let span = info_span!("component", component = %mirror.component.path());
+            $scoreboard.add_component(mirror);
+            tokio::spawn(async move { component.run().await }.instrument(span));
             $data_channels.push(data_channel);
         )+
     };
@@ -425,7 +1115,15 @@ impl Fairing for CORS {
     }
 }
 
-fn add_components(send: Sender<LogEvent>, data_channels: &mut Vec<Sender<Value>>) {
+fn add_components(
+    send: Sender<LogEvent>,
+    data_channels: &mut Vec<Sender<Value>>,
+    scoreboard: &mut Scoreboard,
+    horns: &Horns,
+    possession: &PossessionConfig,
+    bounds: &BoundsConfig,
+    replication: &Replication,
+) {
     use Component as C;
     use GlobalComponent as GC;
     use TeamComponent as TC;
@@ -437,39 +1135,261 @@ fn add_components(send: Sender<LogEvent>, data_channels: &mut Vec<Sender<Value>>
     run_components!(
         send,
         data_channels,
-        GameClock { game_clock_data.clone() },
-        GameDependentClock { C::Global(GC::ShotClock), "shot_clock", shot_clock_data.clone() },
-        StoppageClock { C::Global(GC::StoppageClock), "stoppage_clock", stoppage_clock_data.clone() },
+        scoreboard,
+        GameClock { game_clock_data.clone(), horns.clone(), bounds.clock("game_clock") },
+        GameDependentClock { C::Global(GC::ShotClock), "shot_clock", shot_clock_data.clone(), horns.clone(), bounds.clock("shot_clock") },
+        StoppageClock { C::Global(GC::StoppageClock), "stoppage_clock", stoppage_clock_data.clone(), horns.clone(), bounds.clock("stoppage_clock") },
         Siren { },
-        Counter { C::Global(GC::Period), "period", 1 },
-        Counter { C::Home(TC::Score), "home_score", 0 },
-        Counter { C::Away(TC::Score), "away_score", 0 },
-        TeamFoulCounter { C::Home(TC::TeamFouls), "home_tf" },
-        TeamFoulCounter { C::Away(TC::TeamFouls), "away_tf" },
+        Toggle { C::Global(GC::ShotClockHorn), "shot_clock_horn" },
+        Counter { C::Global(GC::Period), "period", 1, bounds.counter("period") },
+        Counter { C::Home(TC::Score), "home_score", 0, bounds.counter("home_score") },
+        Counter { C::Away(TC::Score), "away_score", 0, bounds.counter("away_score") },
+        Counter { C::Home(TC::TeamFouls), "home_tf", 0, bounds.counter("home_tf") },
+        Counter { C::Away(TC::TeamFouls), "away_tf", 0, bounds.counter("away_tf") },
         Toggle { C::Home(TC::TeamFoulWarning), "home_team_foul_warning" },
         Toggle { C::Away(TC::TeamFoulWarning), "away_team_foul_warning" },
         Toggle { C::Home(TC::TimeOutWarning), "home_team_timeout" },
         Toggle { C::Away(TC::TimeOutWarning), "away_team_timeout" },
-        Label { C::Global(GC::MatchTitle), "match_title", "" },
-        Label { C::Home(TC::TeamName), "home", "Home" },
-        Label { C::Away(TC::TeamName), "away", "Away" },
+        Label { C::Global(GC::MatchTitle), "match_title", "", bounds.label("match_title") },
+        Possession { C::Global(GC::Possession), "possession", possession, replication.clone() },
+        Label { C::Home(TC::TeamName), "home", "Home", bounds.label("home") },
+        Label { C::Away(TC::TeamName), "away", "Away", bounds.label("away") },
+        Label { C::Home(TC::Logo), "home_logo", "", bounds.label("home_logo") },
+        Label { C::Away(TC::Logo), "away_logo", "", bounds.label("away_logo") },
     );
 }
 
+/// Reads an optional section of the Rocket config, e.g. `[default.osc]`.
+fn extract_config<T: DeserializeOwned + Default>(rocket: &Rocket<Build>, key: &str) -> T {
+    let figment = rocket.figment();
+    if figment.find_value(key).is_err() {
+        return T::default();
+    }
+    figment
+        .extract_inner(key)
+        .unwrap_or_else(|e| panic!("invalid `{key}` config: {e}"))
+}
+
 #[launch]
 async fn rocket() -> _ {
+    let rocket = rocket::build();
+    let log_guard = init_logging(&extract_config::<LoggingConfig>(&rocket, "logging"));
+
     let (send, _) = broadcast::channel::<LogEvent>(2048);
     let mut data_channels = vec![];
+    let mut scoreboard = Scoreboard::default();
+
+    let replication = Replication::new(extract_config::<ReplicationConfig>(&rocket, "replication"));
+    let horns = Horns::start(
+        &extract_config::<SirenConfig>(&rocket, "siren"),
+        send.clone(),
+        replication.clone(),
+    );
+    let possession = extract_config::<PossessionConfig>(&rocket, "possession");
+    let bounds = extract_config::<BoundsConfig>(&rocket, "bounds");
+    let shot_clock_max = bounds.clock("shot_clock").map(|max| max.as_millis() as u64);
+    if let (Some(reset), Some(max)) = (possession.shot_clock_reset_ms, shot_clock_max) {
+        if reset > max {
+            panic!("invalid `possession` config: `shot_clock_reset_ms` is above the shot clock's bound");
+        }
+    }
+    add_components(
+        send.clone(),
+        &mut data_channels,
+        &mut scoreboard,
+        &horns,
+        &possession,
+        &bounds,
+        &replication,
+    );
+    let replay = Shareable::from(Replay::new(scoreboard.clone(), create_data_channel()));
 
-    add_components(send.clone(), &mut data_channels);
+    start_secondary(replication.clone(), send.clone());
+    let rules = start_rules(
+        extract_config::<Vec<Rule>>(&rocket, "rules"),
+        scoreboard.clone(),
+        replication.clone(),
+        send.clone(),
+    );
+    let fouls_data = create_data_channel();
+    data_channels.push(fouls_data.clone());
+    let team_fouls = start_team_fouls(
+        extract_config::<TeamFoulsConfig>(&rocket, "team_fouls"),
+        scoreboard.clone(),
+        replication.clone(),
+        send.clone(),
+        fouls_data,
+    );
+    let ticker_data = create_data_channel();
+    data_channels.push(ticker_data.clone());
+    let refresh = DataRefresh::default();
+    let ticker = start_ticker(
+        extract_config::<TickerConfig>(&rocket, "ticker"),
+        scoreboard.clone(),
+        send.clone(),
+        ticker_data,
+        refresh.clone(),
+    );
+    let display_data = create_data_channel();
+    data_channels.push(display_data.clone());
+    let display = start_display(
+        extract_config::<DisplayConfig>(&rocket, "display"),
+        display_data,
+        refresh.clone(),
+    );
+    let assets = Assets::new(extract_config::<AssetsConfig>(&rocket, "assets"));
+    let computed_labels_data = create_data_channel();
+    data_channels.push(computed_labels_data.clone());
+    start_computed_labels(
+        extract_config::<HashMap<String, String>>(&rocket, "computed_labels"),
+        &bounds,
+        scoreboard.clone(),
+        send.clone(),
+        computed_labels_data,
+    );
+    let scoreboard = Shareable::from(scoreboard);
+    let integration_sender =
+        IntegrationSender::new(send.clone(), replication.clone(), scoreboard.clone());
+    start_osc(
+        extract_config::<OscConfig>(&rocket, "osc"),
+        integration_sender.clone(),
+        data_channels.clone(),
+    );
+    start_mqtt(
+        extract_config::<MqttConfig>(&rocket, "mqtt"),
+        integration_sender.clone(),
+        data_channels.clone(),
+    );
+    start_console_inputs(
+        extract_config::<Vec<ConsoleInputConfig>>(&rocket, "console_inputs"),
+        integration_sender.clone(),
+    );
+    start_text_files(
+        extract_config::<TextFilesConfig>(&rocket, "text_files"),
+        data_channels.clone(),
+    );
+    start_serial_outputs(
+        extract_config::<Vec<SerialOutputConfig>>(&rocket, "serial_outputs"),
+        data_channels.clone(),
+    );
+    let midi = start_midi(
+        extract_config::<MidiConfig>(&rocket, "midi"),
+        integration_sender,
+        data_channels.clone(),
+    );
+    let deliveries = start_webhooks(
+        extract_config::<Vec<WebhookConfig>>(&rocket, "webhooks"),
+        replication.clone(),
+        send.clone(),
+        data_channels.clone(),
+    );
+    start_event_logger(scoreboard.clone(), send.clone());
 
-    rocket::build()
+    rocket
         .attach(CORS)
+        .attach(RequestLogger)
+        .manage(log_guard)
         .manage(send)
+        .manage(refresh)
         .manage(data_channels)
-        .mount("/", routes![index, data, echo_stream, reset])
+        .manage(scoreboard)
+        .manage(replay)
+        .manage(replication)
+        .manage(deliveries)
+        .manage(midi)
+        .manage(horns)
+        .manage(rules)
+        .manage(team_fouls)
+        .manage(ticker)
+        .manage(assets)
+        .manage(display)
+        .mount(
+            "/",
+            routes![
+                index,
+                data,
+                prometheus_metrics,
+                echo_stream,
+                reset,
+                events,
+                export_events,
+                score_types
+            ],
+        )
         .mount("/scoreboard", FileServer::from("static"))
         .mount("/_app", FileServer::from("static/_app"))
+        .mount("/feed/", routes![vmix_feed, xml_feed, csv_feed])
+        .mount("/snapshot/", routes![snapshot, restore_snapshot])
+        .mount(
+            "/companion/",
+            routes![
+                companion_action_get,
+                companion_action_post,
+                companion_feedbacks,
+                companion_feedback
+            ],
+        )
+        .mount(
+            "/midi/",
+            routes![midi_mapping, set_midi_mapping, learn_midi],
+        )
+        .mount("/webhooks/", routes![webhook_deliveries])
+        .mount(
+            "/rules/",
+            routes![list_rules, get_rule, enable_rule, disable_rule],
+        )
+        .mount(
+            "/fouls/",
+            routes![foul_status, foul_profiles, set_foul_profile],
+        )
+        .mount(
+            "/display/",
+            routes![
+                active_display,
+                display_layouts,
+                set_display_layout,
+                delete_display_layout,
+                activate_display_layout,
+                display_themes,
+                set_display_theme,
+                delete_display_theme,
+                activate_display_theme
+            ],
+        )
+        .mount(
+            "/assets/",
+            routes![list_assets, get_asset, upload_asset, delete_asset],
+        )
+        .mount(
+            "/ticker/",
+            routes![
+                ticker_status,
+                ticker_items,
+                set_ticker_items,
+                push_ticker_item,
+                remove_ticker_item,
+                interrupt_ticker,
+                clear_urgent_ticker
+            ],
+        )
+        .mount(
+            "/siren/",
+            routes![siren_patterns, play_siren_pattern, press_horn, release_horn],
+        )
+        .mount(
+            "/replication/",
+            routes![replication_status, promote, replication_stream],
+        )
+        .mount(
+            "/replay/",
+            routes![
+                start_replay,
+                step_replay,
+                stop_replay,
+                replay_data,
+                replay_stream
+            ],
+        )
         .mount(
             "/clock/",
             routes![global_clock_event, home_clock_event, away_clock_event],
@@ -482,8 +1402,70 @@ async fn rocket() -> _ {
             "/toggle/",
             routes![global_toggle_event, home_toggle_event, away_toggle_event],
         )
+        .mount("/possession/", routes![global_possession_event])
         .mount(
             "/label/",
             routes![global_label_event, home_label_event, away_label_event],
         )
 }
+
+#[cfg(test)]
+mod tests {
+    use component::label::{InternalLabel, LabelBounds};
+    use scoreboard::ScoreboardComponent;
+
+    use super::*;
+
+    const ID: &str = "3f2a5e1c-8c5b-4c1e-9a57-0d6f3e4b2a10";
+
+    fn ticker(items: Vec<TickerItem>) -> Ticker {
+        let config = TickerConfig {
+            items,
+            only_when_stopped: false,
+        };
+        let (event_sender, _) = broadcast::channel(16);
+        let (data_channel, _) = broadcast::channel(16);
+        let refresh = DataRefresh::default();
+        start_ticker(
+            config,
+            Scoreboard::default(),
+            event_sender,
+            data_channel,
+            refresh,
+        )
+    }
+
+    fn image(path: &str) -> TickerItem {
+        TickerItem {
+            content: TickerContent::Image(path.into()),
+            duration_ms: 60_000,
+        }
+    }
+
+    #[rocket::async_test]
+    async fn keeps_assets_shown_as_logos() {
+        let mut scoreboard = Scoreboard::default();
+        let logo = InternalLabel::new("away_logo".into(), ID.into(), LabelBounds::default());
+        scoreboard.add_component(ScoreboardComponent::new(
+            Component::Away(TeamComponent::Logo),
+            ComponentState::Label(logo),
+        ));
+        let ticker = ticker(vec![]);
+        assert!(asset_in_use(ID, &scoreboard, &ticker).is_some_and(|e| e.contains("logo")));
+        assert_eq!(asset_in_use("other", &scoreboard, &ticker), None);
+    }
+
+    #[rocket::async_test]
+    async fn keeps_assets_shown_in_the_ticker() {
+        let scoreboard = Scoreboard::default();
+        let playlist = ticker(vec![image(&format!("/assets/{ID}"))]);
+        assert!(asset_in_use(ID, &scoreboard, &playlist).is_some_and(|e| e.contains("ticker")));
+
+        let urgent = ticker(vec![image("/static/sponsor.png")]);
+        assert_eq!(asset_in_use(ID, &scoreboard, &urgent), None);
+        urgent.interrupt(image(ID)).unwrap();
+        assert!(asset_in_use(ID, &scoreboard, &urgent).is_some());
+        urgent.clear_urgent();
+        assert_eq!(asset_in_use(ID, &scoreboard, &urgent), None);
+    }
+}
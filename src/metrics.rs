@@ -0,0 +1,178 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    sync::{LazyLock, Mutex},
+    time::Duration,
+};
+
+use tracing::warn;
+
+use crate::event::LogEvent;
+
+pub static METRICS: LazyLock<Metrics> = LazyLock::new(Metrics::new);
+
+/// Cumulative buckets in seconds, as Prometheus expects them.
+#[derive(Debug)]
+pub struct Histogram {
+    bounds: &'static [f64],
+    counts: Vec<u64>,
+    sum: f64,
+    count: u64,
+}
+impl Histogram {
+    fn new(bounds: &'static [f64]) -> Self {
+        Self {
+            bounds,
+            counts: vec![0; bounds.len()],
+            sum: 0.0,
+            count: 0,
+        }
+    }
+    fn observe(&mut self, value: Duration) {
+        let value = value.as_secs_f64();
+        for (bound, count) in self.bounds.iter().zip(&mut self.counts) {
+            if value <= *bound {
+                *count += 1;
+            }
+        }
+        self.sum += value;
+        self.count += 1;
+    }
+    fn render(&self, out: &mut String, name: &str) {
+        for (bound, count) in self.bounds.iter().zip(&self.counts) {
+            let _ = writeln!(out, "{name}_bucket{{le=\"{bound}\"}} {count}");
+        }
+        let _ = writeln!(out, "{name}_bucket{{le=\"+Inf\"}} {}", self.count);
+        let _ = writeln!(out, "{name}_sum {}", self.sum);
+        let _ = writeln!(out, "{name}_count {}", self.count);
+    }
+}
+
+/// Process wide counters for the `/metrics` endpoint.
+#[derive(Debug)]
+pub struct Metrics {
+    events: Mutex<BTreeMap<(String, String), u64>>,
+    lagged: Mutex<BTreeMap<&'static str, u64>>,
+    websocket_clients: Mutex<BTreeMap<&'static str, i64>>,
+    get_data: Mutex<Histogram>,
+    expiry_lateness: Mutex<Histogram>,
+}
+impl Metrics {
+    fn new() -> Self {
+        Self {
+            events: Mutex::default(),
+            lagged: Mutex::default(),
+            websocket_clients: Mutex::default(),
+            get_data: Mutex::new(Histogram::new(&[
+                0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0,
+            ])),
+            expiry_lateness: Mutex::new(Histogram::new(&[
+                0.01, 0.05, 0.1, 0.2, 0.3, 0.5, 1.0, 2.0, 5.0,
+            ])),
+        }
+    }
+    pub fn record_event(&self, event: &LogEvent) {
+        let key = (event.component.path(), event.event.action().to_string());
+        *self.events.lock().unwrap().entry(key).or_default() += 1;
+    }
+    /// Events a broadcast receiver missed because it fell behind.
+    pub fn record_lag(&self, receiver: &'static str, skipped: u64) {
+        warn!(
+            receiver,
+            skipped, "broadcast receiver lagged behind the event channel"
+        );
+        *self.lagged.lock().unwrap().entry(receiver).or_default() += skipped;
+    }
+    pub fn observe_get_data(&self, duration: Duration) {
+        self.get_data.lock().unwrap().observe(duration);
+    }
+    /// How long after a clock ran out its `Expired` event was sent.
+    pub fn observe_expiry_lateness(&self, lateness: Duration) {
+        self.expiry_lateness.lock().unwrap().observe(lateness);
+    }
+    /// Counts a websocket client until the returned guard is dropped.
+    pub fn websocket_client(&self, stream: &'static str) -> WebsocketClient {
+        *self
+            .websocket_clients
+            .lock()
+            .unwrap()
+            .entry(stream)
+            .or_default() += 1;
+        WebsocketClient { stream }
+    }
+
+    /// The Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        header(
+            &mut out,
+            "scoreboard_events_total",
+            "counter",
+            "Events sent through the event channel.",
+        );
+        for ((component, kind), count) in self.events.lock().unwrap().iter() {
+            let labels = format!("component=\"{component}\",kind=\"{kind}\"");
+            let _ = writeln!(out, "scoreboard_events_total{{{labels}}} {count}");
+        }
+        header(
+            &mut out,
+            "scoreboard_broadcast_lagged_total",
+            "counter",
+            "Events skipped by lagging receivers.",
+        );
+        for (receiver, count) in self.lagged.lock().unwrap().iter() {
+            let _ = writeln!(
+                out,
+                "scoreboard_broadcast_lagged_total{{receiver=\"{receiver}\"}} {count}"
+            );
+        }
+        header(
+            &mut out,
+            "scoreboard_websocket_clients",
+            "gauge",
+            "Connected websocket clients.",
+        );
+        for (stream, count) in self.websocket_clients.lock().unwrap().iter() {
+            let _ = writeln!(
+                out,
+                "scoreboard_websocket_clients{{stream=\"{stream}\"}} {count}"
+            );
+        }
+        let name = "scoreboard_get_data_seconds";
+        header(
+            &mut out,
+            name,
+            "histogram",
+            "Time to collect data from all components.",
+        );
+        self.get_data.lock().unwrap().render(&mut out, name);
+        let name = "scoreboard_expiry_lateness_seconds";
+        header(
+            &mut out,
+            name,
+            "histogram",
+            "Delay between a clock running out and its expiry event.",
+        );
+        self.expiry_lateness.lock().unwrap().render(&mut out, name);
+        out
+    }
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {name} {help}");
+    let _ = writeln!(out, "# TYPE {name} {kind}");
+}
+
+pub struct WebsocketClient {
+    stream: &'static str,
+}
+impl Drop for WebsocketClient {
+    fn drop(&mut self) {
+        *METRICS
+            .websocket_clients
+            .lock()
+            .unwrap()
+            .entry(self.stream)
+            .or_default() -= 1;
+    }
+}
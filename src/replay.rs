@@ -0,0 +1,181 @@
+use std::time::{Duration, Instant};
+
+use rocket::tokio::{
+    self,
+    sync::{broadcast::Sender, mpsc},
+    task::JoinHandle,
+    time::sleep,
+};
+use serde::Deserialize;
+use serde_json::Value;
+use uuid::Uuid;
+
+use crate::{
+    component::Component,
+    event::{Event, EventSource, LogEvent, Shareable},
+    scoreboard::Scoreboard,
+};
+
+/// Slowest and fastest a replay may run, as multiples of real time.
+pub const MIN_SPEED: f64 = 0.01;
+pub const MAX_SPEED: f64 = 1000.0;
+
+/// An entry of an exported event log. Timestamps are kept as milliseconds
+/// since they may predate this process.
+#[derive(Debug, Clone, Deserialize)]
+pub struct RecordedEvent {
+    timestamp: u64,
+    log_id: Uuid,
+    component: Component,
+    event: Event,
+    #[serde(default)]
+    source: EventSource,
+    #[serde(default)]
+    score_type: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ReplayBoard {
+    scoreboard: Scoreboard,
+    position: usize,
+    length: usize,
+    /// Replay speed, or `None` when stepping through events by hand.
+    speed: Option<f64>,
+    real_start: Instant,
+    virtual_start: Instant,
+    virtual_now: Instant,
+}
+impl ReplayBoard {
+    fn now(&self) -> Instant {
+        match self.speed {
+            Some(speed) => self.virtual_start + self.real_start.elapsed().mul_f64(speed),
+            None => self.virtual_now,
+        }
+    }
+    pub fn get_data(&self) -> Value {
+        let mut data = self.scoreboard.get_data(self.now());
+        if let Value::Object(map) = &mut data {
+            map.insert(
+                "replay".into(),
+                serde_json::json!({
+                    "position": self.position,
+                    "length": self.length,
+                    "speed": self.speed,
+                }),
+            );
+        }
+        data
+    }
+}
+
+#[derive(Debug)]
+struct ReplaySession {
+    board: Shareable<ReplayBoard>,
+    step: Option<mpsc::Sender<()>>,
+    task: JoinHandle<()>,
+}
+
+/// Runs recorded event logs through a scoreboard kept apart from the live one.
+#[derive(Debug)]
+pub struct Replay {
+    template: Scoreboard,
+    data_channel: Sender<Value>,
+    session: Option<ReplaySession>,
+}
+impl Replay {
+    pub fn new(template: Scoreboard, data_channel: Sender<Value>) -> Self {
+        Self {
+            template,
+            data_channel,
+            session: None,
+        }
+    }
+    pub fn data_channel(&self) -> Sender<Value> {
+        self.data_channel.clone()
+    }
+    pub fn get_data(&self) -> Option<Value> {
+        let session = self.session.as_ref()?;
+        let data = session.board.data.lock().unwrap().get_data();
+        Some(data)
+    }
+    pub fn start(&mut self, recorded: Vec<RecordedEvent>, speed: Option<f64>) {
+        self.stop();
+
+        let real_start = Instant::now();
+        let first_timestamp = recorded.first().map_or(0, |event| event.timestamp);
+        let events: Vec<_> = recorded
+            .into_iter()
+            .map(|event| LogEvent {
+                timestamp: real_start
+                    + Duration::from_millis(event.timestamp.saturating_sub(first_timestamp)),
+                log_id: event.log_id,
+                component: event.component,
+                event: event.event,
+                source: event.source,
+                score_type: event.score_type,
+                depth: 0,
+            })
+            .collect();
+
+        let board = Shareable::from(ReplayBoard {
+            scoreboard: self.template.clone(),
+            position: 0,
+            length: events.len(),
+            speed,
+            real_start,
+            virtual_start: real_start,
+            virtual_now: real_start,
+        });
+        let (step, mut step_recv) = mpsc::channel(16);
+        let data_channel = self.data_channel.clone();
+        let _ = data_channel.send(board.data.lock().unwrap().get_data());
+
+        let task_board = board.clone();
+        let task = tokio::spawn(async move {
+            for event in events {
+                match speed {
+                    Some(speed) => {
+                        let now = task_board.data.lock().unwrap().now();
+                        let wait = event.timestamp.saturating_duration_since(now);
+                        sleep(wait.div_f64(speed)).await;
+                    }
+                    None => {
+                        if step_recv.recv().await.is_none() {
+                            return;
+                        }
+                    }
+                }
+                let data = {
+                    let mut board = task_board.data.lock().unwrap();
+                    board.virtual_now = event.timestamp;
+                    board.position += 1;
+                    board.scoreboard.log_event(event);
+                    board.get_data()
+                };
+                let _ = data_channel.send(data);
+            }
+        });
+
+        self.session = Some(ReplaySession {
+            board,
+            step: speed.is_none().then_some(step),
+            task,
+        });
+    }
+    /// Applies the next event when stepping. Returns false if not stepping.
+    pub fn step(&self) -> bool {
+        let Some(step) = self
+            .session
+            .as_ref()
+            .and_then(|session| session.step.as_ref())
+        else {
+            return false;
+        };
+        step.try_send(()).is_ok()
+    }
+    pub fn stop(&mut self) {
+        if let Some(session) = self.session.take() {
+            session.task.abort();
+        }
+    }
+}
@@ -0,0 +1,260 @@
+use std::{
+    ops::Deref,
+    time::{Duration, Instant},
+};
+
+use rocket::{
+    futures::{SinkExt, StreamExt},
+    http::Status,
+    request::{FromRequest, Outcome, Request},
+    tokio::{
+        self,
+        sync::broadcast::{error::RecvError, Sender},
+        time::{interval, sleep, timeout},
+    },
+};
+use serde::{Deserialize, Serialize};
+use tokio_tungstenite::{connect_async, tungstenite};
+use tracing::{error, warn};
+
+use crate::{
+    event::{EventSource, LogEvent, Shareable},
+    metrics::METRICS,
+    scoreboard::{Scoreboard, Snapshot},
+};
+
+/// The `replication` section of the Rocket config.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ReplicationConfig {
+    /// Address of the primary's replication stream, e.g.
+    /// `ws://10.0.0.2:8000/replication/stream`. Runs as primary when unset.
+    pub primary: Option<String>,
+    pub heartbeat_interval_ms: u64,
+    pub heartbeat_timeout_ms: u64,
+    /// Promote to primary once the primary, having been reached, stays
+    /// silent for `heartbeat_timeout_ms`.
+    pub auto_promote: bool,
+    /// Addresses displays should try when this instance goes away.
+    pub standby: Vec<String>,
+}
+impl Default for ReplicationConfig {
+    fn default() -> Self {
+        Self {
+            primary: None,
+            heartbeat_interval_ms: 1000,
+            heartbeat_timeout_ms: 3000,
+            auto_promote: false,
+            standby: vec![],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum ReplicationMessage {
+    Snapshot(Snapshot),
+    Event(LogEvent),
+    Heartbeat,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum Role {
+    Primary,
+    Secondary,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ReplicationStatus {
+    pub role: Role,
+    pub primary: Option<String>,
+    pub connected: bool,
+    pub standby: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Replication {
+    pub config: ReplicationConfig,
+    pub status: Shareable<ReplicationStatus>,
+}
+impl Replication {
+    pub fn new(config: ReplicationConfig) -> Self {
+        let status = ReplicationStatus {
+            role: match config.primary {
+                Some(_) => Role::Secondary,
+                None => Role::Primary,
+            },
+            primary: config.primary.clone(),
+            connected: false,
+            standby: config.standby.clone(),
+        };
+        Self {
+            config,
+            status: status.into(),
+        }
+    }
+    pub fn status(&self) -> ReplicationStatus {
+        self.status.data.lock().unwrap().clone()
+    }
+    pub fn is_secondary(&self) -> bool {
+        self.status.data.lock().unwrap().role == Role::Secondary
+    }
+    pub fn promote(&self) {
+        let mut status = self.status.data.lock().unwrap();
+        status.role = Role::Primary;
+        status.connected = false;
+    }
+}
+
+/// The event sender, for routes that operate the scoreboard. Secondaries
+/// answer these with 409 Conflict, as their state comes from the primary.
+pub struct Operator<'r>(&'r Sender<LogEvent>);
+impl Deref for Operator<'_> {
+    type Target = Sender<LogEvent>;
+    fn deref(&self) -> &Self::Target {
+        self.0
+    }
+}
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Operator<'r> {
+    type Error = &'static str;
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let rocket = request.rocket();
+        let secondary = rocket
+            .state::<Replication>()
+            .is_some_and(Replication::is_secondary);
+        match (secondary, rocket.state::<Sender<LogEvent>>()) {
+            (true, _) => Outcome::Error((Status::Conflict, "secondary follows its primary")),
+            (false, Some(sender)) => Outcome::Success(Operator(sender)),
+            (false, None) => Outcome::Error((Status::InternalServerError, "no event sender")),
+        }
+    }
+}
+
+/// Follows the configured primary, feeding its events into the local event
+/// channel until this instance gets promoted.
+pub fn start_secondary(replication: Replication, event_sender: Sender<LogEvent>) {
+    let Some(primary) = replication.config.primary.clone() else {
+        return;
+    };
+    let heartbeat_timeout = Duration::from_millis(replication.config.heartbeat_timeout_ms);
+    tokio::spawn(async move {
+        // Never promote before the primary has been reached, e.g. when both
+        // start at once, or two primaries would be running.
+        let mut last_heard: Option<Instant> = None;
+        while replication.is_secondary() {
+            let mut stream = match connect_async(primary.as_str()).await {
+                Ok((stream, _)) => stream,
+                Err(e) => {
+                    warn!(%primary, error = %e, "replication: failed to connect to primary");
+                    if !promote_if_lost(&replication, last_heard, heartbeat_timeout) {
+                        sleep(heartbeat_timeout).await;
+                    }
+                    continue;
+                }
+            };
+            replication.status.data.lock().unwrap().connected = true;
+            last_heard = Some(Instant::now());
+            while replication.is_secondary() {
+                let message = match timeout(heartbeat_timeout, stream.next()).await {
+                    Ok(Some(Ok(tungstenite::Message::Text(text)))) => text,
+                    Ok(Some(Ok(_))) => continue,
+                    Ok(Some(Err(e))) => {
+                        warn!(%primary, error = %e, "replication: stream from primary failed");
+                        break;
+                    }
+                    Ok(None) => break,
+                    Err(_) => {
+                        warn!(%primary, "replication: heartbeat from primary lost");
+                        break;
+                    }
+                };
+                match serde_json::from_str(&message) {
+                    Ok(ReplicationMessage::Snapshot(snapshot)) => {
+                        let source = EventSource::Integration("replication".into());
+                        match snapshot.restore_events(None, source) {
+                            Ok(events) => {
+                                for event in events {
+                                    if let Err(e) = event_sender.send(event) {
+                                        error!(error = %e, "replication: failed to apply snapshot");
+                                    }
+                                }
+                            }
+                            Err(e) => warn!(%primary, error = e, "replication: ignoring snapshot"),
+                        }
+                    }
+                    Ok(ReplicationMessage::Event(event)) => {
+                        if let Err(e) = event_sender.send(event) {
+                            error!(error = %e, "replication: failed to apply event");
+                        }
+                    }
+                    Ok(ReplicationMessage::Heartbeat) => {}
+                    Err(e) => warn!(error = %e, "replication: invalid message"),
+                }
+                last_heard = Some(Instant::now());
+            }
+            replication.status.data.lock().unwrap().connected = false;
+            promote_if_lost(&replication, last_heard, heartbeat_timeout);
+        }
+    });
+}
+
+/// Promotes with `auto_promote` once a primary that was reached has been
+/// silent for the heartbeat timeout. Returns whether it did.
+fn promote_if_lost(
+    replication: &Replication,
+    last_heard: Option<Instant>,
+    heartbeat_timeout: Duration,
+) -> bool {
+    let lost = last_heard.is_some_and(|heard| heard.elapsed() >= heartbeat_timeout);
+    if !replication.config.auto_promote || !lost {
+        return false;
+    }
+    warn!("replication: primary lost, promoting to primary");
+    replication.promote();
+    true
+}
+
+/// Sends a snapshot followed by every event and a regular heartbeat.
+pub async fn serve_secondary(
+    mut stream: ws::stream::DuplexStream,
+    scoreboard: Shareable<Scoreboard>,
+    event_sender: Sender<LogEvent>,
+    heartbeat_interval: Duration,
+) {
+    let mut recv = event_sender.subscribe();
+    let (snapshot, mut applied) = {
+        let scoreboard = scoreboard.data.lock().unwrap();
+        (scoreboard.snapshot(), scoreboard.recent_log_ids(512))
+    };
+    let mut heartbeat = interval(heartbeat_interval);
+    let mut message = ReplicationMessage::Snapshot(snapshot);
+    loop {
+        let text = serde_json::to_string(&message).expect("replication message serializes");
+        if let Err(e) = stream.send(ws::Message::Text(text)).await {
+            warn!(error = %e, "replication: failed to send to secondary");
+            return;
+        }
+        message = loop {
+            tokio::select! {
+                event = recv.recv() => match event {
+                    // Events received before the snapshot was taken are already part of it.
+                    Ok(event) if applied.remove(&event.log_id) => continue,
+                    Ok(event) => {
+                        applied.clear();
+                        break ReplicationMessage::Event(event);
+                    }
+                    Err(RecvError::Closed) => {
+                        error!("replication: event channel closed");
+                        return;
+                    }
+                    Err(RecvError::Lagged(skipped)) => {
+                        METRICS.record_lag("replication", skipped);
+                        let snapshot = scoreboard.data.lock().unwrap().snapshot();
+                        break ReplicationMessage::Snapshot(snapshot);
+                    }
+                },
+                _ = heartbeat.tick() => break ReplicationMessage::Heartbeat,
+            }
+        };
+    }
+}
@@ -0,0 +1,557 @@
+use std::{
+    collections::HashSet,
+    time::{Duration, Instant, SystemTime},
+};
+
+use rocket::tokio::{
+    self, select,
+    sync::broadcast::{error::RecvError, Sender},
+    time::interval,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tracing::{debug, error, warn};
+use uuid::Uuid;
+
+use crate::{
+    component::Component,
+    event::{parse_command, states::ClockState, Event, EventPattern, LogEvent, Shareable},
+    metrics::METRICS,
+    replication::Replication,
+    scoreboard::{ComponentState, Scoreboard},
+};
+
+/// Rules ignore events derived this many times, so rules that trigger each
+/// other can't loop forever.
+const MAX_DEPTH: u32 = 8;
+
+/// Sends events when something happens or a clock reaches a time, provided
+/// all conditions hold. Configured as a list under `rules`; a rule named
+/// like a built in one replaces it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Rule {
+    pub name: String,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    /// Fires on any matching event.
+    #[serde(default)]
+    pub on: Vec<EventPattern>,
+    /// Fires once a running clock counts down past a time.
+    pub at: Option<ClockTrigger>,
+    #[serde(default)]
+    pub conditions: Vec<Condition>,
+    pub actions: Vec<RuleAction>,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClockTrigger {
+    /// Route style component path, e.g. `home/inferiorityclock`.
+    pub clock: String,
+    pub remaining_ms: u64,
+}
+
+/// Compares a component's state: milliseconds remaining for clocks, the
+/// value for counters and labels, and whether toggles are active.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Condition {
+    pub component: String,
+    pub op: Op,
+    pub value: Value,
+    /// Added to numeric states before comparing, e.g. to test whether
+    /// `fouls + 1` is a multiple of 5.
+    #[serde(default)]
+    pub offset: i64,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    MultipleOf,
+}
+
+impl Condition {
+    fn holds(&self, state: &Value) -> bool {
+        let (Some(state), Some(value)) = (state.as_f64(), self.value.as_f64()) else {
+            return match self.op {
+                Op::Eq => *state == self.value,
+                Op::Ne => *state != self.value,
+                _ => false,
+            };
+        };
+        let state = state + self.offset as f64;
+        match self.op {
+            Op::Eq => state == value,
+            Op::Ne => state != value,
+            Op::Lt => state < value,
+            Op::Le => state <= value,
+            Op::Gt => state > value,
+            Op::Ge => state >= value,
+            Op::MultipleOf => value != 0.0 && state % value == 0.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RuleAction {
+    /// Route style command, e.g. `global/gameclock/stop`.
+    pub command: String,
+    pub value: Option<String>,
+}
+
+fn state_value(state: &ComponentState, now: Instant) -> Value {
+    match state {
+        ComponentState::Clock(clock) => json!(clock.get_time_remaining_at(now).as_millis() as u64),
+        ComponentState::Counter(counter) => json!(counter.value),
+        ComponentState::Toggle(toggle) => json!(toggle.is_active()),
+        ComponentState::Label(label) => json!(label.value()),
+        ComponentState::Possession(possession) => json!(possession.state),
+    }
+}
+
+/// The rules the hard-coded components used to apply.
+fn builtin_rules() -> Vec<Rule> {
+    let action = |command: &str| RuleAction {
+        command: command.into(),
+        value: None,
+    };
+    vec![Rule {
+        name: "stoppage_stops_game_clock".into(),
+        enabled: true,
+        on: vec![EventPattern {
+            component: Some("global/stoppageclock".into()),
+            kind: Some("start".into()),
+        }],
+        at: None,
+        conditions: vec![],
+        actions: vec![action("global/gameclock/stop")],
+    }]
+}
+
+/// A rule with its paths resolved.
+struct CompiledRule {
+    name: String,
+    on: Vec<EventPattern>,
+    at: Option<(Component, Duration)>,
+    conditions: Vec<(Component, Condition)>,
+    actions: Vec<(Component, Event)>,
+    /// Events this rule sent and hasn't seen come back yet, which it must
+    /// not react to itself.
+    emitted: HashSet<Uuid>,
+    last_remaining: Option<Duration>,
+}
+impl CompiledRule {
+    fn new(rule: &Rule) -> Result<Self, String> {
+        let component = |path: &str| {
+            Component::from_path(path).ok_or_else(|| format!("unknown component `{path}`"))
+        };
+        let at = match &rule.at {
+            Some(at) => Some((
+                component(&at.clock)?,
+                Duration::from_millis(at.remaining_ms),
+            )),
+            None => None,
+        };
+        if at.is_none() && rule.on.is_empty() {
+            return Err("needs `on` or `at`".into());
+        }
+        for pattern in &rule.on {
+            if let Some(path) = &pattern.component {
+                component(path)?;
+            }
+        }
+        let conditions = rule
+            .conditions
+            .iter()
+            .map(|condition| Ok((component(&condition.component)?, condition.clone())))
+            .collect::<Result<_, String>>()?;
+        let actions = rule
+            .actions
+            .iter()
+            .map(|action| {
+                parse_command(&action.command, action.value.as_deref())
+                    .ok_or_else(|| format!("invalid command `{}`", action.command))
+            })
+            .collect::<Result<_, String>>()?;
+        Ok(Self {
+            name: rule.name.clone(),
+            on: rule.on.clone(),
+            at,
+            conditions,
+            actions,
+            emitted: HashSet::new(),
+            last_remaining: None,
+        })
+    }
+    fn conditions_hold(&self, state: &Scoreboard, now: Instant) -> bool {
+        self.conditions.iter().all(|(component, condition)| {
+            state
+                .find(*component)
+                .is_some_and(|component| condition.holds(&state_value(component, now)))
+        })
+    }
+    /// Whether the trigger clock just counted down past its time.
+    fn crossed(&mut self, state: &Scoreboard, now: Instant) -> bool {
+        let Some((clock, time)) = self.at else {
+            return false;
+        };
+        let Some(ComponentState::Clock(clock)) = state.find(clock) else {
+            return false;
+        };
+        let remaining = clock.get_time_remaining_at(now);
+        let last = self.last_remaining.replace(remaining);
+        matches!(clock.state, ClockState::Running)
+            && last.is_some_and(|last| last > time)
+            && remaining <= time
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RuleStatus {
+    #[serde(flatten)]
+    pub rule: Rule,
+    pub fired: u64,
+    #[serde(with = "serde_millis")]
+    pub last_fired: Option<SystemTime>,
+}
+
+/// Shared view of the running rules for the API.
+#[derive(Debug, Clone)]
+pub struct Rules {
+    status: Shareable<Vec<RuleStatus>>,
+}
+impl Rules {
+    pub fn list(&self) -> Vec<RuleStatus> {
+        self.status.data.lock().unwrap().clone()
+    }
+    pub fn get(&self, name: &str) -> Option<RuleStatus> {
+        let status = self.status.data.lock().unwrap();
+        status
+            .iter()
+            .find(|status| status.rule.name == name)
+            .cloned()
+    }
+    /// Returns false for unknown rules.
+    pub fn set_enabled(&self, name: &str, enabled: bool) -> bool {
+        let mut status = self.status.data.lock().unwrap();
+        let Some(status) = status.iter_mut().find(|status| status.rule.name == name) else {
+            return false;
+        };
+        status.rule.enabled = enabled;
+        true
+    }
+    fn is_enabled(&self, index: usize) -> bool {
+        self.status.data.lock().unwrap()[index].rule.enabled
+    }
+    fn fired(&self, index: usize) {
+        let mut status = self.status.data.lock().unwrap();
+        status[index].fired += 1;
+        status[index].last_fired = Some(SystemTime::now());
+    }
+}
+
+/// Evaluates the rules against a private copy of the scoreboard, so
+/// conditions see the state right after each event. Rules only run on the
+/// primary; secondaries receive the resulting events through replication.
+pub fn start_rules(
+    config: Vec<Rule>,
+    mut state: Scoreboard,
+    replication: Replication,
+    event_sender: Sender<LogEvent>,
+) -> Rules {
+    let mut rules = builtin_rules();
+    for rule in config {
+        match rules.iter_mut().find(|builtin| builtin.name == rule.name) {
+            Some(builtin) => *builtin = rule,
+            None => rules.push(rule),
+        }
+    }
+    let mut compiled: Vec<_> = rules
+        .iter()
+        .map(|rule| {
+            CompiledRule::new(rule)
+                .unwrap_or_else(|e| panic!("invalid `rules` config: rule `{}`: {e}", rule.name))
+        })
+        .collect();
+    let handle = Rules {
+        status: Shareable::from(
+            rules
+                .into_iter()
+                .map(|rule| RuleStatus {
+                    rule,
+                    fired: 0,
+                    last_fired: None,
+                })
+                .collect::<Vec<_>>(),
+        ),
+    };
+
+    let rules = handle.clone();
+    let mut recv = event_sender.subscribe();
+    tokio::spawn(async move {
+        let mut tick = interval(Duration::from_millis(100));
+        loop {
+            let (trigger, now) = select! {
+                event = recv.recv() => match event {
+                    Ok(event) => {
+                        state.apply_event(&event);
+                        let now = event.timestamp;
+                        (Some(event), now)
+                    }
+                    Err(RecvError::Lagged(skipped)) => {
+                        METRICS.record_lag("rules", skipped);
+                        // The skipped events may include ones the rules sent.
+                        compiled.iter_mut().for_each(|rule| rule.emitted.clear());
+                        continue;
+                    }
+                    Err(RecvError::Closed) => break,
+                },
+                _ = tick.tick() => (None, Instant::now()),
+            };
+            if replication.is_secondary() {
+                continue;
+            }
+            if let Some(event) = trigger.as_ref().filter(|event| event.depth >= MAX_DEPTH) {
+                warn!(
+                    component = ?event.component,
+                    depth = event.depth,
+                    "rules: not reacting to a deeply derived event, rules may trigger each other"
+                );
+                for rule in &mut compiled {
+                    rule.emitted.remove(&event.log_id);
+                }
+                continue;
+            }
+            for (index, rule) in compiled.iter_mut().enumerate() {
+                let fires = match &trigger {
+                    Some(LogEvent {
+                        event: Event::Restore(_),
+                        ..
+                    }) => false,
+                    Some(event) => {
+                        !rule.emitted.remove(&event.log_id)
+                            && rule.on.iter().any(|pattern| pattern.matches(event))
+                    }
+                    None => rule.crossed(&state, now),
+                };
+                if !fires || !rules.is_enabled(index) || !rule.conditions_hold(&state, now) {
+                    continue;
+                }
+                debug!(rule = rule.name, "rule fired");
+                rules.fired(index);
+                for (component, event) in &rule.actions {
+                    let event = match &trigger {
+                        Some(trigger) => trigger.derive(*component, event.clone()),
+                        None => LogEvent::new_now(*component, event.clone()),
+                    };
+                    let log_id = event.log_id;
+                    match event_sender.send(event) {
+                        Ok(_) => {
+                            rule.emitted.insert(log_id);
+                        }
+                        Err(e) => error!(error = %e, "rules: failed to send event"),
+                    }
+                }
+            }
+        }
+    });
+    handle
+}
+
+#[cfg(test)]
+mod tests {
+    use rocket::tokio::{
+        sync::broadcast::{self, Receiver},
+        time::timeout,
+    };
+
+    use super::*;
+    use crate::{
+        component::{
+            counter::{CounterBounds, InternalCounter},
+            GlobalComponent, TeamComponent,
+        },
+        event::states::{ClockEvent, CounterEvent},
+        replication::ReplicationConfig,
+        scoreboard::ScoreboardComponent,
+    };
+
+    fn rule(name: &str, on: &str, kind: &str, command: &str) -> Rule {
+        Rule {
+            name: name.into(),
+            enabled: true,
+            on: vec![EventPattern {
+                component: Some(on.into()),
+                kind: Some(kind.into()),
+            }],
+            at: None,
+            conditions: vec![],
+            actions: vec![RuleAction {
+                command: command.into(),
+                value: None,
+            }],
+        }
+    }
+
+    fn condition(op: Op, value: Value, offset: i64) -> Condition {
+        Condition {
+            component: "home/score".into(),
+            op,
+            value,
+            offset,
+        }
+    }
+
+    fn start(config: Vec<Rule>, state: Scoreboard) -> (Sender<LogEvent>, Receiver<LogEvent>) {
+        let (event_sender, events) = broadcast::channel(64);
+        start_rules(
+            config,
+            state,
+            Replication::new(ReplicationConfig::default()),
+            event_sender.clone(),
+        );
+        (event_sender, events)
+    }
+
+    /// Events seen until none arrive for a while.
+    async fn drain(events: &mut Receiver<LogEvent>) -> Vec<LogEvent> {
+        let mut seen = vec![];
+        while let Ok(Ok(event)) = timeout(Duration::from_millis(300), events.recv()).await {
+            seen.push(event);
+        }
+        seen
+    }
+
+    #[test]
+    fn conditions_compare_numbers_and_values() {
+        let holds = |op, value, offset, state: Value| condition(op, value, offset).holds(&state);
+        assert!(holds(Op::Eq, json!(3), 0, json!(3)));
+        assert!(holds(Op::Ne, json!(3), 0, json!(4)));
+        assert!(holds(Op::Lt, json!(3), 0, json!(2)));
+        assert!(!holds(Op::Le, json!(3), 0, json!(4)));
+        assert!(holds(Op::Gt, json!(3), 1, json!(3)));
+        assert!(holds(Op::Ge, json!(3), 0, json!(3)));
+        assert!(holds(Op::MultipleOf, json!(5), 1, json!(9)));
+        assert!(!holds(Op::MultipleOf, json!(5), 0, json!(9)));
+        assert!(!holds(Op::MultipleOf, json!(0), 0, json!(0)));
+        assert!(holds(Op::Eq, json!("HOME"), 0, json!("HOME")));
+        assert!(holds(Op::Eq, json!(true), 0, json!(true)));
+        assert!(!holds(Op::Gt, json!("HOME"), 0, json!("AWAY")));
+    }
+
+    #[test]
+    fn conditions_read_the_scoreboard() {
+        let score = Component::Home(TeamComponent::Score);
+        let mut state = Scoreboard::default();
+        state.add_component(ScoreboardComponent::new(
+            score,
+            ComponentState::Counter(InternalCounter::new(
+                "home_score".into(),
+                4,
+                CounterBounds::default(),
+            )),
+        ));
+        let mut rule = rule("r", "home/score", "increment", "global/gameclock/stop");
+        rule.conditions = vec![condition(Op::Ge, json!(4), 0)];
+        let compiled = CompiledRule::new(&rule).unwrap();
+        assert!(compiled.conditions_hold(&state, Instant::now()));
+
+        rule.conditions = vec![condition(Op::Gt, json!(4), 0)];
+        let compiled = CompiledRule::new(&rule).unwrap();
+        assert!(!compiled.conditions_hold(&state, Instant::now()));
+
+        rule.conditions[0].component = "away/score".into();
+        let compiled = CompiledRule::new(&rule).unwrap();
+        assert!(
+            !compiled.conditions_hold(&state, Instant::now()),
+            "missing components never hold"
+        );
+    }
+
+    #[test]
+    fn rejects_rules_that_cannot_run() {
+        let mut no_trigger = rule("r", "home/score", "increment", "global/gameclock/stop");
+        no_trigger.on.clear();
+        assert!(CompiledRule::new(&no_trigger).is_err());
+        let unknown = rule("r", "home/nothing", "increment", "global/gameclock/stop");
+        assert!(CompiledRule::new(&unknown).is_err());
+        let invalid = rule("r", "home/score", "increment", "global/gameclock/explode");
+        assert!(CompiledRule::new(&invalid).is_err());
+    }
+
+    #[rocket::async_test]
+    async fn stoppage_stops_game_clock() {
+        let (event_sender, mut events) = start(vec![], Scoreboard::default());
+        let stoppage = Component::Global(GlobalComponent::StoppageClock);
+        let start = LogEvent::new_now(stoppage, Event::Clock(ClockEvent::Start(None)));
+        event_sender.send(start).unwrap();
+
+        let seen = drain(&mut events).await;
+        assert_eq!(seen.len(), 2, "{seen:?}");
+        let stop = &seen[1];
+        assert_eq!(
+            stop.component,
+            Component::Global(GlobalComponent::GameClock)
+        );
+        assert!(matches!(stop.event, Event::Clock(ClockEvent::Stop(_))));
+        assert_eq!(stop.depth, 1);
+    }
+
+    #[rocket::async_test]
+    async fn fires_only_on_matching_events() {
+        let config = vec![rule(
+            "score_stops_clock",
+            "home/score",
+            "increment",
+            "global/gameclock/stop",
+        )];
+        let (event_sender, mut events) = start(config, Scoreboard::default());
+        let score = Component::Home(TeamComponent::Score);
+        let away = Component::Away(TeamComponent::Score);
+        for (component, event) in [
+            (score, CounterEvent::Decrement),
+            (away, CounterEvent::Increment),
+            (score, CounterEvent::Increment),
+        ] {
+            event_sender
+                .send(LogEvent::new_now(component, Event::Counter(event)))
+                .unwrap();
+        }
+
+        let seen = drain(&mut events).await;
+        assert_eq!(seen.len(), 4, "{seen:?}");
+        assert_eq!(
+            seen[3].component,
+            Component::Global(GlobalComponent::GameClock)
+        );
+    }
+
+    #[rocket::async_test]
+    async fn rules_triggering_each_other_stop_at_max_depth() {
+        let config = vec![
+            rule("ping", "home/score", "increment", "away/score/increment"),
+            rule("pong", "away/score", "increment", "home/score/increment"),
+        ];
+        let (event_sender, mut events) = start(config, Scoreboard::default());
+        let score = Component::Home(TeamComponent::Score);
+        event_sender
+            .send(LogEvent::new_now(
+                score,
+                Event::Counter(CounterEvent::Increment),
+            ))
+            .unwrap();
+
+        let seen = drain(&mut events).await;
+        let depths: Vec<_> = seen.iter().map(|event| event.depth).collect();
+        assert_eq!(depths, (0..=MAX_DEPTH).collect::<Vec<_>>());
+    }
+}
@@ -1,71 +1,538 @@
-use std::{collections::HashMap, time::Instant};
+use std::{
+    collections::{BTreeMap, HashSet, VecDeque},
+    ops::Range,
+    time::{Duration, Instant, SystemTime},
+};
 
+use rocket::tokio::{
+    self,
+    sync::broadcast::{error::RecvError, Sender},
+};
+use serde::{Deserialize, Serialize};
 use serde_json::{Map, Value};
+use tracing::{debug, debug_span};
 use uuid::Uuid;
 
 use crate::{
-    component::{Component, DataComponent},
-    event::{Event, EventListener, EventLogger, LogEvent},
+    component::{
+        clock::{follows_game_clock, format_time_remaining, ClockComponent},
+        counter::InternalCounter,
+        label::InternalLabel,
+        possession::InternalPossession,
+        toggle::InteralToggle,
+        Component, GlobalComponent,
+    },
+    event::{
+        event_matches, instant_from_millis, states::ClockState, Event, EventSource, LogEvent,
+        Shareable,
+    },
+    metrics::METRICS,
 };
 
-pub trait ScoreboardComponent: EventListener + DataComponent {}
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum ComponentState {
+    Clock(ClockComponent),
+    Counter(InternalCounter),
+    Toggle(InteralToggle),
+    Label(InternalLabel),
+    Possession(InternalPossession),
+}
+impl ComponentState {
+    fn process_event(&mut self, event: &LogEvent) {
+        match self {
+            Self::Clock(clock) => clock.process_event(event),
+            Self::Counter(counter) => counter.process_event(event),
+            Self::Toggle(toggle) => toggle.process_event(event),
+            Self::Label(label) => label.process_event(event),
+            Self::Possession(possession) => possession.process_event(event),
+        }
+    }
+    fn get_data(&self, now: Instant) -> Value {
+        match self {
+            Self::Clock(clock) => clock.get_data(now),
+            Self::Counter(counter) => counter.get_data(),
+            Self::Toggle(toggle) => toggle.get_data(),
+            Self::Label(label) => label.get_data(),
+            Self::Possession(possession) => possession.get_data(),
+        }
+    }
+}
+
+/// Synchronous copy of a running component, fed from the same event stream.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScoreboardComponent {
+    pub component: Component,
+    #[serde(skip)]
+    follows_game_clock: bool,
+    pub state: ComponentState,
+}
+impl ScoreboardComponent {
+    pub fn new(component: Component, state: ComponentState) -> Self {
+        Self {
+            component,
+            follows_game_clock: false,
+            state,
+        }
+    }
+    pub fn following_game_clock(mut self) -> Self {
+        self.follows_game_clock = true;
+        self
+    }
+    fn is_relevant(&self, event: &LogEvent) -> bool {
+        self.component.is_event_component_relevant(&event.component)
+            || (self.follows_game_clock && follows_game_clock(event))
+    }
+}
+
+/// Where the match stood when an event was logged.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GameContext {
+    pub period: u64,
+    pub game_time: String,
+    #[serde(with = "serde_millis")]
+    pub game_clock: Duration,
+    pub game_clock_state: ClockState,
+    #[serde(with = "serde_millis")]
+    pub shot_clock: Duration,
+    pub shot_clock_state: ClockState,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LoggedEvent {
+    #[serde(flatten)]
+    pub event: LogEvent,
+    pub context: GameContext,
+    /// Data of the affected components once the event was applied.
+    pub state: Value,
+}
+
+/// Filters for browsing the event log.
+#[derive(Debug, Default, FromForm)]
+pub struct EventQuery {
+    /// Route style component path, e.g. `home/score`.
+    pub component: Option<String>,
+    /// Either the event type (`counter`) or the specific event (`increment`).
+    pub kind: Option<String>,
+    /// Milliseconds since the Unix epoch, inclusive.
+    pub from: Option<u64>,
+    /// Milliseconds since the Unix epoch, exclusive.
+    pub to: Option<u64>,
+    pub period: Option<u64>,
+    pub score_type: Option<String>,
+    pub offset: Option<usize>,
+    pub limit: Option<usize>,
+}
+impl EventQuery {
+    fn matches(&self, entry: &LoggedEvent) -> bool {
+        let event = &entry.event;
+        if !event_matches(self.component.as_deref(), self.kind.as_deref(), event) {
+            return false;
+        }
+        if let Some(from) = self.from.and_then(instant_from_millis) {
+            if event.timestamp < from {
+                return false;
+            }
+        }
+        if let Some(to) = self.to.and_then(instant_from_millis) {
+            if event.timestamp >= to {
+                return false;
+            }
+        }
+        if let Some(score_type) = &self.score_type {
+            if event.score_type.as_ref() != Some(score_type) {
+                return false;
+            }
+        }
+        self.period
+            .is_none_or(|period| entry.context.period == period)
+    }
+}
+
+/// Totals for one score type, net of corrections.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ScoreTypeTotal {
+    pub count: i64,
+    pub points: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct EventPage {
+    pub total: usize,
+    pub offset: usize,
+    pub events: Vec<LoggedEvent>,
+}
 
-#[derive(Default, Debug)]
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+/// Events kept in the log; the oldest are dropped beyond this.
+pub const EVENT_LOG_SIZE: usize = 50_000;
+
+/// The state of every component at one point in time.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub version: u32,
+    #[serde(with = "serde_millis")]
+    pub taken_at: SystemTime,
+    pub components: Vec<ScoreboardComponent>,
+}
+
+impl Snapshot {
+    /// Events that bring every component back to the captured state. Running
+    /// clocks resume from their remaining time when the snapshot was taken.
+    /// Fails for snapshots of another version.
+    pub fn restore_events(
+        self,
+        ts: Option<usize>,
+        source: EventSource,
+    ) -> Result<Vec<LogEvent>, String> {
+        if self.version != SNAPSHOT_VERSION {
+            return Err(format!(
+                "snapshot version {} isn't {SNAPSHOT_VERSION}",
+                self.version
+            ));
+        }
+        let age = SystemTime::now()
+            .duration_since(self.taken_at)
+            .unwrap_or_default();
+        let taken_at = Instant::now().checked_sub(age).unwrap_or_else(Instant::now);
+        let restored_at = ts
+            .and_then(|ts| instant_from_millis(ts as u64))
+            .unwrap_or_else(Instant::now);
+        Ok(self
+            .components
+            .into_iter()
+            .map(|mut component| {
+                if let ComponentState::Clock(clock) = &mut component.state {
+                    clock.rebase(taken_at, restored_at);
+                }
+                component
+            })
+            .map(|component| LogEvent {
+                source: source.clone(),
+                ..LogEvent::new(
+                    component.component,
+                    Event::Restore(component.state),
+                    ts,
+                    None,
+                )
+            })
+            .collect())
+    }
+}
+
+#[derive(Debug, Clone, Default)]
 pub struct Scoreboard {
-    event_log: Vec<LogEvent>,
-    components: Vec<Box<dyn ScoreboardComponent>>,
-    listeners: HashMap<Component, Vec<usize>>,
+    event_log: VecDeque<LoggedEvent>,
+    /// How many events were dropped from the front of the log.
+    dropped_events: usize,
+    components: Vec<ScoreboardComponent>,
 }
 impl Scoreboard {
-    pub fn add_component<C: ScoreboardComponent + 'static>(
-        &mut self,
-        component: C,
-        listening: &[Component],
-    ) {
-        let component_index = self.components.len();
-        self.components.push(Box::from(component));
-        for listen in listening {
-            let list = self.listeners.entry(*listen).or_default();
-            list.push(component_index);
+    pub fn add_component(&mut self, component: ScoreboardComponent) {
+        self.components.push(component);
+    }
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            version: SNAPSHOT_VERSION,
+            taken_at: SystemTime::now(),
+            components: self.components.clone(),
         }
     }
-}
-impl DataComponent for Scoreboard {
-    fn get_data(&self) -> serde_json::Value {
+    pub fn recent_log_ids(&self, count: usize) -> HashSet<Uuid> {
+        let log = self.event_log.iter().rev().take(count);
+        log.map(|entry| entry.event.log_id).collect()
+    }
+    /// How many events were ever logged, including dropped ones.
+    pub fn logged_events(&self) -> usize {
+        self.dropped_events + self.event_log.len()
+    }
+    /// The logged events numbered `range`, counting from the first ever
+    /// logged and skipping any since dropped, and where the next range starts.
+    pub fn logged_range(&self, range: Range<usize>) -> (Vec<LoggedEvent>, usize) {
+        let start = range.start.max(self.dropped_events);
+        let end = range.end.min(self.logged_events()).max(start);
+        let events = self
+            .event_log
+            .range(start - self.dropped_events..end - self.dropped_events)
+            .cloned()
+            .collect();
+        (events, end)
+    }
+    pub fn find(&self, component: Component) -> Option<&ComponentState> {
+        self.components
+            .iter()
+            .find(|c| c.component == component)
+            .map(|c| &c.state)
+    }
+    /// Why an event can't be applied to its component, if it can't.
+    pub fn validate(&self, event: &LogEvent) -> Result<(), String> {
+        match self.find(event.component) {
+            Some(ComponentState::Counter(counter)) => counter.validate(event),
+            Some(ComponentState::Clock(clock)) => clock.validate(event),
+            Some(ComponentState::Label(label)) => label.validate(event),
+            _ => Ok(()),
+        }
+    }
+    fn clock_at(&self, component: GlobalComponent, now: Instant) -> (Duration, ClockState) {
+        match self.find(Component::Global(component)) {
+            Some(ComponentState::Clock(clock)) => (clock.get_time_remaining_at(now), clock.state),
+            _ => (Duration::from_secs(0), ClockState::Stopped),
+        }
+    }
+    pub fn context_at(&self, now: Instant) -> GameContext {
+        let period = match self.find(Component::Global(GlobalComponent::Period)) {
+            Some(ComponentState::Counter(counter)) => counter.value,
+            _ => 0,
+        };
+        let (game_clock, game_clock_state) = self.clock_at(GlobalComponent::GameClock, now);
+        let (shot_clock, shot_clock_state) = self.clock_at(GlobalComponent::ShotClock, now);
+        GameContext {
+            period,
+            game_time: format!("P{period} {}", format_time_remaining(game_clock)),
+            game_clock,
+            game_clock_state,
+            shot_clock,
+            shot_clock_state,
+        }
+    }
+    pub fn get_data(&self, now: Instant) -> Value {
         let mut data_map = Map::<String, Value>::default();
         for component in &self.components {
-            match component.get_data() {
-                Value::Object(map) => data_map.extend(map.into_iter()),
+            match component.state.get_data(now) {
+                Value::Object(map) => data_map.extend(map),
                 _ => panic!("Unknown JSON data type"),
             }
         }
-        serde_json::Value::Object(data_map)
+        Value::Object(data_map)
     }
-}
-impl EventLogger for Scoreboard {
-    fn log_event(&mut self, component: Component, event: Event) {
-        let Some(listeners) = self.listeners.get(&component) else {
-            eprintln!("There are no listeners for {:?}", component);
-            return;
-        };
-        let log_event = LogEvent {
-            timestamp: Instant::now(),
-            log_id: Uuid::new_v4(),
-            component,
+    /// Updates the components without logging, returning the data of
+    /// those affected.
+    pub fn apply_event(&mut self, event: &LogEvent) -> Value {
+        let mut state = Map::<String, Value>::default();
+        for component in &mut self.components {
+            if !component.is_relevant(event) {
+                continue;
+            }
+            component.state.process_event(event);
+            if let Value::Object(map) = component.state.get_data(event.timestamp) {
+                state.extend(map);
+            }
+        }
+        Value::Object(state)
+    }
+    pub fn log_event(&mut self, event: LogEvent) {
+        let context = self.context_at(event.timestamp);
+        let state = self.apply_event(&event);
+        if self.event_log.len() >= EVENT_LOG_SIZE {
+            self.event_log.pop_front();
+            self.dropped_events += 1;
+        }
+        self.event_log.push_back(LoggedEvent {
             event,
-        };
-        for &listener in listeners {
-            let Some(listener_component) = self.components.get_mut(listener) else {
-                eprintln!("component {listener} doesn't exist");
+            context,
+            state,
+        });
+        // push to DB
+    }
+    /// Tagged counter changes by component path and score type. Subtracting
+    /// with a tag takes the score back off, e.g. after a disallowed try.
+    /// Only events still in the log are counted.
+    pub fn score_types(&self) -> BTreeMap<String, BTreeMap<String, ScoreTypeTotal>> {
+        let mut totals = BTreeMap::<String, BTreeMap<String, ScoreTypeTotal>>::new();
+        for entry in &self.event_log {
+            let event = &entry.event;
+            let (Some(score_type), Event::Counter(counter)) = (&event.score_type, &event.event)
+            else {
+                continue;
+            };
+            let Some(delta) = counter.delta() else {
                 continue;
             };
-            listener_component.notify(&log_event);
+            let total = totals
+                .entry(event.component.path())
+                .or_default()
+                .entry(score_type.clone())
+                .or_default();
+            total.count += delta.signum() as i64;
+            total.points = total
+                .points
+                .saturating_add(delta.clamp(i64::MIN.into(), i64::MAX.into()) as i64);
         }
-        self.event_log.push(log_event);
-        // push to DB
+        totals
+    }
+    pub fn query_events(&self, query: &EventQuery) -> EventPage {
+        let matching = self.event_log.iter().filter(|entry| query.matches(entry));
+        let offset = query.offset.unwrap_or(0);
+        EventPage {
+            total: matching.clone().count(),
+            offset,
+            events: matching
+                .skip(offset)
+                .take(query.limit.unwrap_or(100))
+                .cloned()
+                .collect(),
+        }
+    }
+}
+
+pub fn start_event_logger(scoreboard: Shareable<Scoreboard>, event_sender: Sender<LogEvent>) {
+    let mut recv = event_sender.subscribe();
+    tokio::spawn(async move {
+        loop {
+            match recv.recv().await {
+                Ok(log_event) => {
+                    let span = debug_span!(
+                        "event",
+                        log_id = %log_event.log_id,
+                        component = %log_event.component.path(),
+                        kind = log_event.event.action(),
+                    );
+                    let _enter = span.enter();
+                    debug!(source = ?log_event.source, "logging event");
+                    METRICS.record_event(&log_event);
+                    scoreboard.data.lock().unwrap().log_event(log_event)
+                }
+                Err(RecvError::Closed) => break,
+                Err(RecvError::Lagged(skipped)) => METRICS.record_lag("event_logger", skipped),
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use rocket::tokio::sync::broadcast;
+
+    use super::*;
+    use crate::{
+        component::{
+            clock::GameClock,
+            counter::CounterBounds,
+            label::{InternalLabel, LabelBounds},
+            TeamComponent,
+        },
+        event::states::{ClockEvent, CounterEvent, LabelEvent},
+        replication::{Replication, ReplicationConfig},
+        siren::{Horns, SirenConfig},
+    };
+
+    fn score_board() -> Scoreboard {
+        let mut scoreboard = Scoreboard::default();
+        scoreboard.add_component(ScoreboardComponent::new(
+            Component::Home(TeamComponent::Score),
+            ComponentState::Counter(InternalCounter::new(
+                "home_score".into(),
+                0,
+                CounterBounds::default(),
+            )),
+        ));
+        scoreboard
+    }
+
+    fn increment() -> LogEvent {
+        LogEvent::new_now(
+            Component::Home(TeamComponent::Score),
+            Event::Counter(CounterEvent::Increment),
+        )
+    }
+
+    #[test]
+    fn drops_the_oldest_events_past_the_log_size() {
+        let mut scoreboard = score_board();
+        for _ in 0..EVENT_LOG_SIZE + 10 {
+            scoreboard.log_event(increment());
+        }
+        assert_eq!(scoreboard.event_log.len(), EVENT_LOG_SIZE);
+        assert_eq!(scoreboard.logged_events(), EVENT_LOG_SIZE + 10);
+
+        let (events, next) = scoreboard.logged_range(0..15);
+        assert_eq!(events.len(), 5, "dropped events are skipped");
+        assert_eq!(events[0].state["home_score"], 11);
+        assert_eq!(next, 15);
+
+        let (events, next) = scoreboard.logged_range(EVENT_LOG_SIZE..EVENT_LOG_SIZE + 100);
+        assert_eq!(events.len(), 10);
+        assert_eq!(next, EVENT_LOG_SIZE + 10);
+    }
+
+    /// A score, a team name and the game clock, as they start out.
+    fn game_board() -> Scoreboard {
+        let (event_sender, _) = broadcast::channel(16);
+        let replication = Replication::new(ReplicationConfig::default());
+        let horns = Horns::start(&SirenConfig::default(), event_sender.clone(), replication);
+        let game_clock = GameClock::new(
+            event_sender,
+            broadcast::channel(16).0,
+            broadcast::channel(16).0,
+            horns,
+            None,
+        );
+        let mut scoreboard = score_board();
+        scoreboard.add_component(ScoreboardComponent::new(
+            Component::Home(TeamComponent::TeamName),
+            ComponentState::Label(InternalLabel::new(
+                "home_name".into(),
+                "HOME".into(),
+                LabelBounds::default(),
+            )),
+        ));
+        scoreboard.add_component(game_clock.mirror());
+        scoreboard
+    }
+
+    fn game_clock_remaining(scoreboard: &Scoreboard, now: Instant) -> Duration {
+        match scoreboard.find(Component::Global(GlobalComponent::GameClock)) {
+            Some(ComponentState::Clock(clock)) => clock.get_time_remaining_at(now),
+            state => panic!("no game clock: {state:?}"),
+        }
+    }
+
+    #[rocket::async_test]
+    async fn snapshots_round_trip() {
+        let clock = Component::Global(GlobalComponent::GameClock);
+        let mut scoreboard = game_board();
+        scoreboard.log_event(increment());
+        scoreboard.log_event(LogEvent::new_now(
+            Component::Home(TeamComponent::TeamName),
+            Event::Label(LabelEvent::Set("LIONS".into())),
+        ));
+        scoreboard.log_event(LogEvent::new_now(
+            clock,
+            Event::Clock(ClockEvent::Set(Duration::from_secs(600))),
+        ));
+        // Running for 20 seconds by the time the snapshot is taken.
+        scoreboard.log_event(LogEvent {
+            timestamp: Instant::now() - Duration::from_secs(20),
+            ..LogEvent::new_now(clock, Event::Clock(ClockEvent::Start(None)))
+        });
+        let mut snapshot = scoreboard.snapshot();
+        // Restored 10 seconds after it was taken, which the clock mustn't count.
+        snapshot.taken_at -= Duration::from_secs(10);
+        let json = serde_json::to_string(&snapshot).unwrap();
+        let snapshot: Snapshot = serde_json::from_str(&json).unwrap();
+
+        let mut restored = game_board();
+        for event in snapshot.restore_events(None, EventSource::System).unwrap() {
+            restored.log_event(event);
+        }
+        let now = Instant::now();
+        let data = restored.get_data(now);
+        assert_eq!(data["home_score"], 1);
+        assert_eq!(data["home_name"], "LIONS");
+        assert_eq!(data["game_clock"]["state"], "Running");
+        let remaining = game_clock_remaining(&restored, now);
+        let expected = Duration::from_secs(590);
+        assert!(
+            remaining.abs_diff(expected) < Duration::from_millis(500),
+            "{remaining:?} left instead of {expected:?}"
+        );
+        assert!(game_clock_remaining(&scoreboard, now) < remaining);
+    }
+
+    #[test]
+    fn rejects_snapshots_of_another_version() {
+        let mut snapshot = score_board().snapshot();
+        snapshot.version = SNAPSHOT_VERSION + 1;
+        assert!(snapshot.restore_events(None, EventSource::System).is_err());
     }
-    // fn register_listener(&mut self, component: Component, listener: &'a mut dyn EventListener) {
-    //     let list = self.listeners.entry(component).or_default();
-    //     list.push(listener);
-    // }
 }
@@ -0,0 +1,276 @@
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    time::Duration,
+};
+
+use rocket::{
+    request::FromParam,
+    tokio::{
+        self, select,
+        sync::{
+            broadcast::{error::RecvError, Sender},
+            mpsc,
+        },
+        time::sleep,
+    },
+};
+use serde::{Deserialize, Serialize};
+use strum::{EnumIter, EnumString, IntoEnumIterator, ParseError};
+use tracing::{debug, error, warn};
+
+use crate::{
+    component::{Component, GlobalComponent},
+    event::{states::ToggleEvent, Event, EventPattern, LogEvent},
+    metrics::METRICS,
+    replication::Replication,
+};
+
+/// A horn output, driven through its toggle component.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, EnumString, EnumIter)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case", ascii_case_insensitive)]
+pub enum Horn {
+    Game,
+    ShotClock,
+}
+impl Horn {
+    pub fn component(&self) -> Component {
+        match self {
+            Horn::Game => Component::Global(GlobalComponent::Siren),
+            Horn::ShotClock => Component::Global(GlobalComponent::ShotClockHorn),
+        }
+    }
+}
+impl<'a> FromParam<'a> for Horn {
+    type Error = ParseError;
+    fn from_param(param: &'a str) -> Result<Self, Self::Error> {
+        param.parse()
+    }
+}
+
+/// A named pattern played on a horn.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HornCue {
+    pub pattern: String,
+    #[serde(default = "default_horn")]
+    pub horn: Horn,
+}
+
+fn default_horn() -> Horn {
+    Horn::Game
+}
+
+/// Plays a pattern whenever a matching event is sent.
+#[derive(Debug, Clone, Deserialize)]
+pub struct EventCue {
+    #[serde(flatten)]
+    pub events: EventPattern,
+    #[serde(flatten)]
+    pub cue: HornCue,
+}
+
+/// The `siren` section of the Rocket config.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct SirenConfig {
+    /// Alternating on and off times in milliseconds, starting with on.
+    /// Merged over the built in `period_end`, `shot_clock` and
+    /// `timeout_warning` patterns.
+    pub patterns: HashMap<String, Vec<u64>>,
+    /// Pattern played when a clock runs out, by clock name, e.g.
+    /// `game_clock = { pattern = "period_end" }`.
+    pub expiry: HashMap<String, HornCue>,
+    pub cues: Vec<EventCue>,
+    /// A held horn is released after this long, should the release be lost.
+    pub max_hold_ms: u64,
+}
+impl Default for SirenConfig {
+    fn default() -> Self {
+        let cue = |pattern: &str, horn| HornCue {
+            pattern: pattern.into(),
+            horn,
+        };
+        let timeout_warning = |team: &str| EventCue {
+            events: EventPattern {
+                component: Some(format!("{team}/timeoutwarning")),
+                kind: Some("activate".into()),
+            },
+            cue: cue("timeout_warning", Horn::Game),
+        };
+        Self {
+            patterns: HashMap::new(),
+            expiry: HashMap::from([
+                ("game_clock".into(), cue("period_end", Horn::Game)),
+                ("stoppage_clock".into(), cue("period_end", Horn::Game)),
+                ("shot_clock".into(), cue("shot_clock", Horn::ShotClock)),
+            ]),
+            cues: vec![timeout_warning("home"), timeout_warning("away")],
+            max_hold_ms: 10_000,
+        }
+    }
+}
+impl SirenConfig {
+    fn patterns(&self) -> BTreeMap<String, Vec<u64>> {
+        let mut patterns = BTreeMap::from([
+            ("period_end".into(), vec![2000]),
+            ("shot_clock".into(), vec![250, 150, 250]),
+            ("timeout_warning".into(), vec![400]),
+        ]);
+        patterns.extend(self.patterns.clone());
+        patterns
+    }
+}
+
+#[derive(Debug)]
+enum HornCommand {
+    Play(Vec<u64>),
+    Press,
+    Release,
+}
+
+/// Owns one horn, turning commands into timed activate and deactivate
+/// events. A new command replaces whatever was playing.
+async fn run_horn(
+    horn: Horn,
+    mut commands: mpsc::Receiver<HornCommand>,
+    event_sender: Sender<LogEvent>,
+    max_hold: Duration,
+) {
+    let mut steps = VecDeque::<(bool, Duration)>::new();
+    let mut sounding = false;
+    let mut set = |on: bool| {
+        if on == sounding {
+            return;
+        }
+        sounding = on;
+        let event = match on {
+            true => ToggleEvent::Activate,
+            false => ToggleEvent::Deactivate,
+        };
+        let event = LogEvent::new_now(horn.component(), Event::Toggle(event));
+        if let Err(e) = event_sender.send(event) {
+            error!(?horn, error = %e, "failed to send horn event");
+        }
+    };
+    loop {
+        let command = match steps.pop_front() {
+            Some((on, duration)) => {
+                set(on);
+                select! {
+                    command = commands.recv() => command,
+                    _ = sleep(duration) => continue,
+                }
+            }
+            None => {
+                set(false);
+                commands.recv().await
+            }
+        };
+        steps.clear();
+        match command {
+            Some(HornCommand::Play(pattern)) => {
+                let pattern = pattern.into_iter().enumerate();
+                steps.extend(pattern.map(|(i, ms)| (i % 2 == 0, Duration::from_millis(ms))));
+            }
+            Some(HornCommand::Press) => steps.push_back((true, max_hold)),
+            Some(HornCommand::Release) => {}
+            None => break,
+        }
+    }
+}
+
+/// Plays siren patterns on the horns, shared by clocks and routes.
+#[derive(Debug, Clone)]
+pub struct Horns {
+    patterns: BTreeMap<String, Vec<u64>>,
+    expiry: HashMap<String, HornCue>,
+    players: HashMap<Horn, mpsc::Sender<HornCommand>>,
+    replication: Replication,
+}
+impl Horns {
+    pub fn start(
+        config: &SirenConfig,
+        event_sender: Sender<LogEvent>,
+        replication: Replication,
+    ) -> Self {
+        let max_hold = Duration::from_millis(config.max_hold_ms);
+        let players = Horn::iter()
+            .map(|horn| {
+                let (send, recv) = mpsc::channel(16);
+                tokio::spawn(run_horn(horn, recv, event_sender.clone(), max_hold));
+                (horn, send)
+            })
+            .collect();
+        let horns = Self {
+            patterns: config.patterns(),
+            expiry: config.expiry.clone(),
+            players,
+            replication,
+        };
+        horns.start_cues(config.cues.clone(), event_sender);
+        horns
+    }
+    fn start_cues(&self, cues: Vec<EventCue>, event_sender: Sender<LogEvent>) {
+        if cues.is_empty() {
+            return;
+        }
+        let horns = self.clone();
+        let mut recv = event_sender.subscribe();
+        tokio::spawn(async move {
+            loop {
+                let event = match recv.recv().await {
+                    Ok(event) => event,
+                    Err(RecvError::Lagged(skipped)) => {
+                        METRICS.record_lag("siren_cues", skipped);
+                        continue;
+                    }
+                    Err(RecvError::Closed) => break,
+                };
+                for cue in cues.iter().filter(|cue| cue.events.matches(&event)) {
+                    horns.cue(&cue.cue);
+                }
+            }
+        });
+    }
+    /// Secondaries leave the horns and clock expiry to their primary, whose
+    /// events they follow.
+    pub fn is_secondary(&self) -> bool {
+        self.replication.is_secondary()
+    }
+    fn command(&self, horn: Horn, command: HornCommand) {
+        if self.is_secondary() {
+            debug!(?horn, "secondary: leaving horn to the primary");
+            return;
+        }
+        if let Err(e) = self.players[&horn].try_send(command) {
+            warn!(?horn, error = %e, "horn command dropped");
+        }
+    }
+    pub fn patterns(&self) -> &BTreeMap<String, Vec<u64>> {
+        &self.patterns
+    }
+    /// The cue for a clock running out, by clock name.
+    pub fn expiry_cue(&self, clock: &str) -> Option<HornCue> {
+        self.expiry.get(clock).cloned()
+    }
+    /// Returns false for unknown patterns.
+    pub fn play(&self, horn: Horn, pattern: &str) -> bool {
+        let Some(pattern) = self.patterns.get(pattern) else {
+            return false;
+        };
+        self.command(horn, HornCommand::Play(pattern.clone()));
+        true
+    }
+    pub fn cue(&self, cue: &HornCue) {
+        if !self.play(cue.horn, &cue.pattern) {
+            warn!(pattern = cue.pattern, "unknown siren pattern");
+        }
+    }
+    /// Sounds the horn until released, for manual press-and-hold buttons.
+    pub fn press(&self, horn: Horn) {
+        self.command(horn, HornCommand::Press);
+    }
+    pub fn release(&self, horn: Horn) {
+        self.command(horn, HornCommand::Release);
+    }
+}
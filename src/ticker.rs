@@ -0,0 +1,359 @@
+use std::time::{Duration, Instant};
+
+use rocket::tokio::{
+    self, select,
+    sync::broadcast::{error::RecvError, Sender},
+    time::interval,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::{
+    event::{states::ClockState, DataRefresh, LogEvent, Shareable},
+    metrics::METRICS,
+    scoreboard::Scoreboard,
+};
+
+const TICK: Duration = Duration::from_millis(100);
+
+/// What a playlist item shows: a message, or an image such as a sponsor
+/// logo referenced by path or asset id.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TickerContent {
+    Text(String),
+    Image(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TickerItem {
+    #[serde(flatten)]
+    pub content: TickerContent,
+    pub duration_ms: u64,
+}
+impl TickerItem {
+    fn check(&self) -> Result<(), String> {
+        match self.duration_ms {
+            0 => Err("duration_ms must be above 0".into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// The `ticker` section of the Rocket config.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct TickerConfig {
+    pub items: Vec<TickerItem>,
+    /// Pauses the rotation while the game clock runs.
+    pub only_when_stopped: bool,
+}
+impl Default for TickerConfig {
+    fn default() -> Self {
+        Self {
+            items: vec![],
+            only_when_stopped: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TickerStatus {
+    /// What the displays should show now, if anything.
+    pub item: Option<TickerItem>,
+    /// Position of `item` in the playlist, unless it is urgent.
+    pub index: Option<usize>,
+    pub urgent: bool,
+}
+
+#[derive(Debug, Clone)]
+struct TickerState {
+    items: Vec<TickerItem>,
+    index: usize,
+    /// How long the current item has been on screen.
+    shown: Duration,
+    urgent: Option<(TickerItem, Instant)>,
+    /// Whether the game allows the playlist to show.
+    active: bool,
+}
+impl TickerState {
+    /// Hides the playlist while the game clock runs, if it must wait.
+    fn follow_clock(&mut self, game_clock: ClockState, only_when_stopped: bool) {
+        self.active = !(only_when_stopped && matches!(game_clock, ClockState::Running));
+    }
+    fn advance(&mut self, by: Duration) {
+        if !self.active || self.items.is_empty() {
+            return;
+        }
+        self.index %= self.items.len();
+        self.shown += by;
+        let duration = Duration::from_millis(self.items[self.index].duration_ms);
+        if self.shown >= duration {
+            self.index = (self.index + 1) % self.items.len();
+            self.shown = Duration::ZERO;
+        }
+    }
+    fn restart(&mut self) {
+        self.index = 0;
+        self.shown = Duration::ZERO;
+    }
+    fn status(&mut self, now: Instant) -> TickerStatus {
+        if self.urgent.as_ref().is_some_and(|(_, until)| now >= *until) {
+            self.urgent = None;
+        }
+        if let Some((item, _)) = &self.urgent {
+            return TickerStatus {
+                item: Some(item.clone()),
+                index: None,
+                urgent: true,
+            };
+        }
+        let item = self.items.get(self.index).filter(|_| self.active);
+        TickerStatus {
+            item: item.cloned(),
+            index: item.map(|_| self.index),
+            urgent: false,
+        }
+    }
+}
+
+/// The sponsor and message playlist, rotating between play.
+#[derive(Debug, Clone)]
+pub struct Ticker {
+    state: Shareable<TickerState>,
+    refresh: DataRefresh,
+}
+impl Ticker {
+    pub fn status(&self) -> TickerStatus {
+        self.state.data.lock().unwrap().status(Instant::now())
+    }
+    pub fn items(&self) -> Vec<TickerItem> {
+        self.state.data.lock().unwrap().items.clone()
+    }
+    /// Replaces the playlist and starts it from the top.
+    pub fn set_items(&self, items: Vec<TickerItem>) -> Result<(), String> {
+        items.iter().try_for_each(TickerItem::check)?;
+        let mut state = self.state.data.lock().unwrap();
+        state.items = items;
+        state.restart();
+        self.refresh.notify();
+        Ok(())
+    }
+    pub fn push(&self, item: TickerItem) -> Result<(), String> {
+        item.check()?;
+        self.state.data.lock().unwrap().items.push(item);
+        self.refresh.notify();
+        Ok(())
+    }
+    /// Returns the removed item, or `None` if there is no such index.
+    pub fn remove(&self, index: usize) -> Option<TickerItem> {
+        let mut state = self.state.data.lock().unwrap();
+        if index >= state.items.len() {
+            return None;
+        }
+        let item = state.items.remove(index);
+        if index < state.index {
+            state.index -= 1;
+        } else if index == state.index {
+            state.shown = Duration::ZERO;
+        }
+        if state.index >= state.items.len() {
+            state.index = 0;
+        }
+        self.refresh.notify();
+        Some(item)
+    }
+    /// Shows `item` over the playlist for its duration, even during play.
+    pub fn interrupt(&self, item: TickerItem) -> Result<(), String> {
+        item.check()?;
+        let until = Instant::now() + Duration::from_millis(item.duration_ms);
+        self.state.data.lock().unwrap().urgent = Some((item, until));
+        self.refresh.notify();
+        Ok(())
+    }
+    /// Returns false if nothing urgent was showing.
+    pub fn clear_urgent(&self) -> bool {
+        let mut state = self.state.data.lock().unwrap();
+        state.status(Instant::now());
+        let cleared = state.urgent.take().is_some();
+        if cleared {
+            self.refresh.notify();
+        }
+        cleared
+    }
+}
+
+/// Rotates the playlist, following the game clock on a private copy of the
+/// scoreboard, and puts the current item into the data as `ticker`. Data
+/// streams are refreshed whenever that item changes.
+pub fn start_ticker(
+    config: TickerConfig,
+    mut scoreboard: Scoreboard,
+    event_sender: Sender<LogEvent>,
+    data_channel: Sender<Value>,
+    refresh: DataRefresh,
+) -> Ticker {
+    if let Err(e) = config.items.iter().try_for_each(TickerItem::check) {
+        panic!("invalid `ticker` config: {e}");
+    }
+    let only_when_stopped = config.only_when_stopped;
+    let ticker = Ticker {
+        state: TickerState {
+            items: config.items,
+            index: 0,
+            shown: Duration::ZERO,
+            urgent: None,
+            active: true,
+        }
+        .into(),
+        refresh,
+    };
+
+    let data_ticker = ticker.clone();
+    let mut data_recv = data_channel.subscribe();
+    tokio::spawn(async move {
+        loop {
+            let Ok(Value::Null) = data_recv.recv().await else {
+                continue;
+            };
+            let _ = data_channel.send(json!({ "ticker": data_ticker.status() }));
+        }
+    });
+
+    let task_ticker = ticker.clone();
+    let mut recv = event_sender.subscribe();
+    tokio::spawn(async move {
+        let ticker = task_ticker;
+        let mut tick = interval(TICK);
+        let mut last = ticker.status();
+        loop {
+            select! {
+                event = recv.recv() => match event {
+                    Ok(event) => {
+                        scoreboard.apply_event(&event);
+                    }
+                    Err(RecvError::Lagged(skipped)) => METRICS.record_lag("ticker", skipped),
+                    Err(RecvError::Closed) => break,
+                },
+                _ = tick.tick() => {
+                    let context = scoreboard.context_at(Instant::now());
+                    let status = {
+                        let mut state = ticker.state.data.lock().unwrap();
+                        state.follow_clock(context.game_clock_state, only_when_stopped);
+                        state.advance(TICK);
+                        state.status(Instant::now())
+                    };
+                    if status != last {
+                        last = status;
+                        ticker.refresh.notify();
+                    }
+                }
+            }
+        }
+    });
+    ticker
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(text: &str) -> TickerItem {
+        TickerItem {
+            content: TickerContent::Text(text.into()),
+            duration_ms: 1000,
+        }
+    }
+
+    /// A ticker showing `c` of `a`, `b`, `c`, `d`, half way through.
+    fn ticker() -> Ticker {
+        Ticker {
+            state: TickerState {
+                items: ["a", "b", "c", "d"].map(text).into(),
+                index: 2,
+                shown: Duration::from_millis(500),
+                urgent: None,
+                active: true,
+            }
+            .into(),
+            refresh: DataRefresh::default(),
+        }
+    }
+
+    fn showing(ticker: &Ticker) -> (Option<TickerItem>, usize, Duration) {
+        let state = ticker.state.data.lock().unwrap();
+        (
+            state.items.get(state.index).cloned(),
+            state.index,
+            state.shown,
+        )
+    }
+
+    #[test]
+    fn removing_before_the_current_item_keeps_it_showing() {
+        let ticker = ticker();
+        assert_eq!(ticker.remove(0), Some(text("a")));
+        let half = Duration::from_millis(500);
+        assert_eq!(showing(&ticker), (Some(text("c")), 1, half));
+    }
+
+    #[test]
+    fn removing_the_current_item_shows_the_next_from_the_start() {
+        let ticker = ticker();
+        assert_eq!(ticker.remove(2), Some(text("c")));
+        assert_eq!(showing(&ticker), (Some(text("d")), 2, Duration::ZERO));
+        assert_eq!(ticker.remove(2), Some(text("d")));
+        assert_eq!(showing(&ticker), (Some(text("a")), 0, Duration::ZERO));
+    }
+
+    #[test]
+    fn removing_after_the_current_item_changes_nothing_shown() {
+        let ticker = ticker();
+        assert_eq!(ticker.remove(3), Some(text("d")));
+        let half = Duration::from_millis(500);
+        assert_eq!(showing(&ticker), (Some(text("c")), 2, half));
+        assert_eq!(ticker.remove(3), None);
+    }
+
+    #[test]
+    fn pauses_while_the_game_clock_runs() {
+        let ticker = ticker();
+        let mut state = ticker.state.data.lock().unwrap();
+        state.follow_clock(ClockState::Running, true);
+        state.advance(Duration::from_secs(5));
+        let status = state.status(Instant::now());
+        assert_eq!((status.item, status.index), (None, None));
+
+        state.follow_clock(ClockState::Stopped, true);
+        assert_eq!(
+            state.status(Instant::now()).index,
+            Some(2),
+            "resumes in place"
+        );
+        state.advance(Duration::from_millis(500));
+        assert_eq!(state.status(Instant::now()).index, Some(3));
+
+        state.follow_clock(ClockState::Running, false);
+        state.advance(Duration::from_secs(1));
+        assert_eq!(state.status(Instant::now()).index, Some(0));
+    }
+
+    #[test]
+    fn urgent_items_expire() {
+        let ticker = ticker();
+        let urgent = TickerItem {
+            content: TickerContent::Text("goal".into()),
+            duration_ms: 200,
+        };
+        ticker.interrupt(urgent.clone()).unwrap();
+        let status = ticker.status();
+        assert!(status.urgent);
+        assert_eq!((status.item, status.index), (Some(urgent), None));
+
+        let later = Instant::now() + Duration::from_millis(200);
+        let status = ticker.state.data.lock().unwrap().status(later);
+        assert!(!status.urgent);
+        assert_eq!((status.item, status.index), (Some(text("c")), Some(2)));
+        assert!(!ticker.clear_urgent(), "nothing left to clear");
+    }
+}
@@ -0,0 +1,123 @@
+//! Runs a primary and a secondary as separate processes and checks how the
+//! secondary follows, refuses operators and takes over.
+
+use std::{
+    env, fs,
+    io::{Read, Write},
+    net::{TcpListener, TcpStream},
+    path::PathBuf,
+    process::{Child, Command, Stdio},
+    thread::sleep,
+    time::{Duration, Instant},
+};
+
+use serde_json::Value;
+
+struct Instance {
+    child: Child,
+    port: u16,
+    dir: PathBuf,
+}
+impl Instance {
+    fn start(name: &str, port: u16, replication: &str) -> Self {
+        let dir = env::temp_dir().join(format!("replication-{name}-{port}"));
+        // The scoreboard UI is served from here.
+        fs::create_dir_all(dir.join("static/_app")).unwrap();
+        let child = Command::new(env!("CARGO_BIN_EXE_backend"))
+            .current_dir(&dir)
+            .env("ROCKET_PORT", port.to_string())
+            .env("ROCKET_REPLICATION", replication)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .unwrap();
+        Self { child, port, dir }
+    }
+    fn request(&self, method: &str, path: &str) -> Option<(u16, String)> {
+        let mut stream = TcpStream::connect(("127.0.0.1", self.port)).ok()?;
+        stream.set_read_timeout(Some(Duration::from_secs(5))).ok()?;
+        write!(
+            stream,
+            "{method} {path} HTTP/1.1\r\nHost: localhost\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+        )
+        .ok()?;
+        let mut response = String::new();
+        stream.read_to_string(&mut response).ok()?;
+        let status = response.split(' ').nth(1)?.parse().ok()?;
+        let body = response.split_once("\r\n\r\n")?.1.to_string();
+        Some((status, body))
+    }
+    fn get(&self, path: &str) -> Option<Value> {
+        let (_, body) = self.request("GET", path)?;
+        serde_json::from_str(&body).ok()
+    }
+    fn post(&self, path: &str) -> Option<u16> {
+        self.request("POST", path).map(|(status, _)| status)
+    }
+    fn role(&self) -> Option<Value> {
+        self.get("/replication/")
+            .map(|status| status["role"].clone())
+    }
+}
+impl Drop for Instance {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+fn free_port() -> u16 {
+    TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port()
+}
+
+fn wait_for(what: &str, mut check: impl FnMut() -> bool) {
+    let deadline = Instant::now() + Duration::from_secs(15);
+    while !check() {
+        assert!(Instant::now() < deadline, "timed out waiting for {what}");
+        sleep(Duration::from_millis(100));
+    }
+}
+
+#[test]
+fn secondary_follows_and_takes_over() {
+    let primary_port = free_port();
+    let secondary = Instance::start(
+        "secondary",
+        free_port(),
+        &format!(
+            "{{primary=\"ws://127.0.0.1:{primary_port}/replication/stream\",\
+             heartbeat_timeout_ms=1000,auto_promote=true}}"
+        ),
+    );
+    wait_for("secondary to start", || secondary.role().is_some());
+
+    // A primary that was never reached isn't lost.
+    sleep(Duration::from_millis(2500));
+    assert_eq!(secondary.role(), Some("Secondary".into()));
+
+    let primary = Instance::start("primary", primary_port, "{heartbeat_interval_ms=200}");
+    wait_for("secondary to connect", || {
+        secondary
+            .get("/replication/")
+            .is_some_and(|status| status["connected"] == true)
+    });
+
+    assert_eq!(primary.post("/counter/home/score/increment"), Some(200));
+    wait_for("the score to reach the secondary", || {
+        secondary
+            .get("/data")
+            .is_some_and(|data| data["home_score"] == 1)
+    });
+    assert_eq!(secondary.post("/counter/home/score/increment"), Some(409));
+
+    drop(primary);
+    wait_for("the secondary to promote", || {
+        secondary.role() == Some("Primary".into())
+    });
+    assert_eq!(secondary.post("/counter/home/score/increment"), Some(200));
+}